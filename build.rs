@@ -0,0 +1,12 @@
+/*
+    Sets the `kernel` cfg when building for the bare-metal target (the kernel
+    itself and `make test`), but not for the host unit tests of `make unit`.
+    Code that needs the real machine (inline asm, linker symbols, the entry
+    point) is marked `#[cfg(kernel)]`; its host stand-ins `#[cfg(not(kernel))]`.
+*/
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(kernel)");
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("none") {
+        println!("cargo::rustc-cfg=kernel");
+    }
+}
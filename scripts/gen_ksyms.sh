@@ -0,0 +1,36 @@
+#!/bin/sh
+# Turn the function symbols of a linked kernel into a NASM source for the
+# .ksyms section read by src/debug/ksyms.rs:
+#
+#   dd count
+#   dd addr, name_offset      ; count entries, sorted by address
+#   db "name", 0              ; NUL-terminated names, offsets from the start
+#
+# Usage: gen_ksyms.sh kernel.elf > ksyms.asm
+set -e
+
+NM=${NM:-nm}
+
+"$NM" -n -C --defined-only "$1" | awk '
+	BEGIN { n = 0 }
+	# Code symbols only (t/T, and weak ones); data is never a return address.
+	NF >= 3 && $1 ~ /^[0-9a-fA-F]+$/ && $2 ~ /^[tTwW]$/ {
+		name = $0
+		sub(/^[^ ]+ [^ ]+ /, "", name)
+		# NASM backquoted strings: escape backslashes and backquotes.
+		gsub(/\\/, "\\\\", name)
+		gsub(/`/, "\\`", name)
+		addr[n] = $1
+		names[n] = name
+		n++
+	}
+	END {
+		print "section .ksyms progbits alloc noexec nowrite align=4"
+		print "ksyms:"
+		printf "\tdd %d\n", n
+		for (i = 0; i < n; i++)
+			printf "\tdd 0x%s, .n%d - ksyms\n", addr[i], i
+		for (i = 0; i < n; i++)
+			printf ".n%d:\tdb `%s`, 0\n", i, names[i]
+	}
+'
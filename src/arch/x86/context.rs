@@ -0,0 +1,71 @@
+/*
+    Kernel thread context switch (cdecl):
+
+        kfs_switch_context(old_esp: *mut usize, new_esp: usize)
+
+    Pushes the callee-saved registers on the current stack, stores ESP in
+    *old_esp, loads new_esp and pops the same frame from there, so `ret`
+    resumes the other thread where it last switched away. A thread that never
+    ran gets an equivalent frame from `init_stack`.
+*/
+#[cfg(kernel)]
+core::arch::global_asm!(
+    ".global kfs_switch_context",
+    "kfs_switch_context:",
+    "    mov eax, [esp + 4]",
+    "    mov edx, [esp + 8]",
+    "    push ebp",
+    "    push ebx",
+    "    push esi",
+    "    push edi",
+    "    mov [eax], esp",
+    "    mov esp, edx",
+    "    pop edi",
+    "    pop esi",
+    "    pop ebx",
+    "    pop ebp",
+    "    ret",
+);
+
+#[cfg(kernel)]
+extern "C" {
+    fn kfs_switch_context(old_esp: *mut usize, new_esp: usize);
+}
+
+/// Save the current context into `*old_esp` and resume the one saved at `new_esp`.
+///
+/// # Safety
+/// Interrupts must be disabled, and `new_esp` must come from a previous switch
+/// or from `init_stack`.
+#[cfg(kernel)]
+pub unsafe fn switch_context(old_esp: *mut usize, new_esp: usize) {
+    kfs_switch_context(old_esp, new_esp);
+}
+
+/// Host unit tests never spawn threads: the boot thread is the only one to pick.
+///
+/// # Safety
+/// Nothing to uphold, it panics.
+#[cfg(not(kernel))]
+pub unsafe fn switch_context(_old_esp: *mut usize, _new_esp: usize) {
+    panic!("context switch in a host test");
+}
+
+/// Builds the initial frame on a fresh stack so that switching to it enters `entry`.
+///
+/// # Safety
+/// `top` must be the (exclusive) end of a writable stack of at least 32 bytes.
+pub unsafe fn init_stack(top: *mut u8, entry: extern "C" fn() -> !) -> usize {
+    // Aligned as if `entry` had been called: ESP + 4 is 16-byte aligned.
+    let mut sp = (top as usize & !0xF) as *mut usize;
+    sp = sp.sub(1);
+    sp.write(0); // return address of `entry`, which never returns
+    sp = sp.sub(1);
+    sp.write(entry as usize);
+    for _ in 0..4 {
+        // edi, esi, ebx, ebp
+        sp = sp.sub(1);
+        sp.write(0);
+    }
+    sp as usize
+}
@@ -1,8 +1,3 @@
-use core::arch::asm;
-use core::ptr;
-
-use crate::println;
-
 /*
     Descriptor limit for a flat 4 GiB segment (20-bit limit replicated with granularity bit).
 */
@@ -13,11 +8,6 @@ const LIMIT_4GB: u32 = 0x000F_FFFF;
 */
 const GRANULARITY_FLAGS: u8 = 0b1100;
 
-/*
-    Physical address where we copy the GDT before loading it.
-*/
-const GDT_PHYS_ADDR: u32 = 0x0000_0800;
-
 /*
     Selectors/index for the descriptors we build inside the GDT.
 */
@@ -28,11 +18,6 @@ pub(crate) const USER_CODE_SELECTOR: u16 = (4 << 3) | 0b11;
 pub(crate) const USER_DATA_SELECTOR: u16 = (5 << 3) | 0b11;
 pub(crate) const USER_STACK_SELECTOR: u16 = (6 << 3) | 0b11;
 
-extern "C" {
-    static stack_bottom: u8;
-    static stack_top: u8;
-}
-
 #[repr(C, align(8))]
 #[derive(Clone, Copy)]
 pub struct GdtEntry(u64);
@@ -80,7 +65,7 @@ const fn stack_segment(ring: u8) -> GdtEntry {
     Template table that we copy to physical 0x800 before loading it.
 */
 #[used]
-static GDT_TEMPLATE: [GdtEntry; 7] = [
+pub(super) static GDT_TEMPLATE: [GdtEntry; 7] = [
     GdtEntry(0),
     code_segment(0),
     data_segment(0),
@@ -90,116 +75,47 @@ static GDT_TEMPLATE: [GdtEntry; 7] = [
     stack_segment(3),
 ];
 
-/*
-    GDTR-compatible pointer (limit + base) passed to the `lgdt` instruction.
-*/
-#[repr(C, packed)]
-struct DescriptorTablePointer {
-    limit: u16,
-    base: u32,
-}
-
-#[repr(C, packed)]
-struct FarPointer {
-    offset: u32,
-    selector: u16,
-}
-
-pub fn init_with_entry(entry: extern "C" fn() -> !) -> ! {
-    unsafe { init_gdt_and_jump(entry) }
-}
-
-unsafe fn init_gdt_and_jump(entry: extern "C" fn() -> !) -> ! {
-    /*
-        Copy the 7 entries towards 0x800
-    */
-    let gdt_destination = GDT_PHYS_ADDR as *mut GdtEntry;
-    ptr::copy_nonoverlapping(GDT_TEMPLATE.as_ptr(), gdt_destination, GDT_TEMPLATE.len());
-
-    /*
-        Building a GDTR pointer
-    */
-    let gdt_ptr = DescriptorTablePointer {
-        limit: (core::mem::size_of::<[GdtEntry; 7]>() - 1) as u16,
-        base: GDT_PHYS_ADDR,
-    };
-
-    /*
-        Far pointer used to reload CS and validate the new GDT.
-    */
-    let entry_ptr = FarPointer {
-        offset: entry as u32,
-        selector: KERNEL_CODE_SELECTOR,
-    };
-
-    load_gdt_and_segments(&gdt_ptr, &entry_ptr);
-}
-
-unsafe fn load_gdt_and_segments(
-    gdt_ptr: &DescriptorTablePointer,
-    entry: &FarPointer,
-) -> ! {
-    asm!(
-        "cli",
-        "lgdt [{gdt_ptr}]",
-        "mov ax, {data_sel}",
-        "mov ds, ax",
-        "mov es, ax",
-        "mov fs, ax",
-        "mov gs, ax",
-        "mov ax, {stack_sel}",
-        "mov ss, ax",
-        "lea esp, [{stack_ptr}]",
-        "ljmp [{entry}]",
-        gdt_ptr = in(reg) gdt_ptr,
-        entry = in(reg) entry,
-        data_sel = const KERNEL_DATA_SELECTOR,
-        stack_sel = const KERNEL_STACK_SELECTOR,
-        stack_ptr = sym stack_top,
-        options(noreturn),
-    );
-}
-
-pub fn print_stack() {
-    let (bottom, top) = unsafe { stack_bounds() };
-    let esp = current_stack_pointer();
-
-    println!(
-        "Kernel stack range: {:#010X} - {:#010X}",
-        bottom, top
-    );
-    println!("Current ESP: {:#010X}", esp);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if esp < bottom || esp >= top {
-        println!("ESP is outside of the kernel stack!");
-        return;
+    #[test_case]
+    fn kernel_segments_are_flat_ring0() {
+        assert_eq!(code_segment(0).0, 0x00CF_9A00_0000_FFFF);
+        assert_eq!(data_segment(0).0, 0x00CF_9200_0000_FFFF);
+        assert_eq!(stack_segment(0).0, 0x00CF_9600_0000_FFFF);
     }
 
-    let mut addr = esp;
-    let mut count = 0;
-    const STACK_DUMP_ENTRIES: usize = 8;
-    while addr < top && count < STACK_DUMP_ENTRIES {
-        let value = unsafe { core::ptr::read(addr as *const u32) };
-        println!("{:#010X}: {:#010X}", addr, value);
-        addr += 4;
-        count += 1;
+    #[test_case]
+    fn user_segments_are_flat_ring3() {
+        assert_eq!(code_segment(3).0, 0x00CF_FA00_0000_FFFF);
+        assert_eq!(data_segment(3).0, 0x00CF_F200_0000_FFFF);
+        assert_eq!(stack_segment(3).0, 0x00CF_F600_0000_FFFF);
     }
-}
 
-/*
-    Translate the assembly labels into usable Rust addresses
-*/
-unsafe fn stack_bounds() -> (u32, u32) {
-    (
-        &stack_bottom as *const u8 as u32,
-        &stack_top as *const u8 as u32,
-    )
-}
+    #[test_case]
+    fn base_and_limit_are_split_across_the_descriptor() {
+        let entry = GdtEntry::new(0x1234_5678, 0xA_BCDE, 0x92, 0b0100);
+        assert_eq!(entry.0, 0x124A_9234_5678_BCDE);
+    }
 
-fn current_stack_pointer() -> u32 {
-    let esp: u32;
-    unsafe {
-        asm!("mov {0}, esp", out(reg) esp);
+    #[test_case]
+    fn selectors_index_the_template() {
+        let index = |selector: u16| (selector >> 3) as usize;
+        assert_eq!(GDT_TEMPLATE[0].0, 0);
+        assert_eq!(
+            GDT_TEMPLATE[index(KERNEL_CODE_SELECTOR)].0,
+            code_segment(0).0
+        );
+        assert_eq!(
+            GDT_TEMPLATE[index(KERNEL_DATA_SELECTOR)].0,
+            data_segment(0).0
+        );
+        assert_eq!(
+            GDT_TEMPLATE[index(KERNEL_STACK_SELECTOR)].0,
+            stack_segment(0).0
+        );
+        assert_eq!(GDT_TEMPLATE[index(USER_CODE_SELECTOR)].0, code_segment(3).0);
+        assert_eq!(USER_DATA_SELECTOR & 0b11, 3);
     }
-    esp
 }
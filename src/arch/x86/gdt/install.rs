@@ -0,0 +1,142 @@
+/*
+    Loading the GDT and switching to the kernel stack, plus the stack dump
+    that goes with it. Kernel-only: the descriptors themselves are in gdt.rs.
+*/
+use core::arch::asm;
+use core::ptr;
+
+use super::gdt::{
+    GdtEntry, GDT_TEMPLATE, KERNEL_CODE_SELECTOR, KERNEL_DATA_SELECTOR, KERNEL_STACK_SELECTOR,
+};
+use crate::debug::ksyms::{self, Symbolized};
+use crate::println;
+
+/*
+    Physical address where we copy the GDT before loading it.
+*/
+const GDT_PHYS_ADDR: u32 = 0x0000_0800;
+
+extern "C" {
+    static stack_bottom: u8;
+    static stack_top: u8;
+}
+
+/*
+    GDTR-compatible pointer (limit + base) passed to the `lgdt` instruction.
+*/
+#[repr(C, packed)]
+struct DescriptorTablePointer {
+    limit: u16,
+    base: u32,
+}
+
+#[repr(C, packed)]
+struct FarPointer {
+    offset: u32,
+    selector: u16,
+}
+
+pub fn init_with_entry(entry: extern "C" fn() -> !) -> ! {
+    unsafe { init_gdt_and_jump(entry) }
+}
+
+unsafe fn init_gdt_and_jump(entry: extern "C" fn() -> !) -> ! {
+    /*
+        Copy the 7 entries towards 0x800
+    */
+    let gdt_destination = GDT_PHYS_ADDR as *mut GdtEntry;
+    ptr::copy_nonoverlapping(GDT_TEMPLATE.as_ptr(), gdt_destination, GDT_TEMPLATE.len());
+
+    /*
+        Building a GDTR pointer
+    */
+    let gdt_ptr = DescriptorTablePointer {
+        limit: (core::mem::size_of::<[GdtEntry; 7]>() - 1) as u16,
+        base: GDT_PHYS_ADDR,
+    };
+
+    /*
+        Far pointer used to reload CS and validate the new GDT.
+    */
+    let entry_ptr = FarPointer {
+        offset: entry as u32,
+        selector: KERNEL_CODE_SELECTOR,
+    };
+
+    load_gdt_and_segments(&gdt_ptr, &entry_ptr);
+}
+
+unsafe fn load_gdt_and_segments(gdt_ptr: &DescriptorTablePointer, entry: &FarPointer) -> ! {
+    asm!(
+        "cli",
+        "lgdt [{gdt_ptr}]",
+        "mov ax, {data_sel}",
+        "mov ds, ax",
+        "mov es, ax",
+        "mov fs, ax",
+        "mov gs, ax",
+        "mov ax, {stack_sel}",
+        "mov ss, ax",
+        "lea esp, [{stack_ptr}]",
+        // The old frames are gone: end the frame-pointer chain here.
+        "xor ebp, ebp",
+        "ljmp [{entry}]",
+        gdt_ptr = in(reg) gdt_ptr,
+        entry = in(reg) entry,
+        data_sel = const KERNEL_DATA_SELECTOR,
+        stack_sel = const KERNEL_STACK_SELECTOR,
+        stack_ptr = sym stack_top,
+        options(noreturn),
+    );
+}
+
+pub fn print_stack() {
+    let (bottom, top) = unsafe { stack_bounds() };
+    let esp = current_stack_pointer();
+
+    println!("Kernel stack range: {:#010X} - {:#010X}", bottom, top);
+    println!("Current ESP: {:#010X}", esp);
+
+    if esp < bottom || esp >= top {
+        println!("ESP is outside of the kernel stack!");
+        return;
+    }
+
+    let mut addr = esp;
+    let mut count = 0;
+    const STACK_DUMP_ENTRIES: usize = 8;
+    while addr < top && count < STACK_DUMP_ENTRIES {
+        let value = unsafe { core::ptr::read(addr as *const u32) };
+        // Words pointing into the kernel code are likely return addresses.
+        if ksyms::in_text(value as usize) {
+            println!(
+                "{:#010X}: {:#010X} <{}>",
+                addr,
+                value,
+                Symbolized(value as usize)
+            );
+        } else {
+            println!("{:#010X}: {:#010X}", addr, value);
+        }
+        addr += 4;
+        count += 1;
+    }
+}
+
+/*
+    Translate the assembly labels into usable Rust addresses
+*/
+unsafe fn stack_bounds() -> (u32, u32) {
+    (
+        &stack_bottom as *const u8 as u32,
+        &stack_top as *const u8 as u32,
+    )
+}
+
+fn current_stack_pointer() -> u32 {
+    let esp: u32;
+    unsafe {
+        asm!("mov {0}, esp", out(reg) esp);
+    }
+    esp
+}
@@ -1,3 +1,6 @@
 pub mod gdt;
+#[cfg(kernel)]
+mod install;
 
-pub use gdt::{init_with_entry, print_stack};
+#[cfg(kernel)]
+pub use install::{init_with_entry, print_stack};
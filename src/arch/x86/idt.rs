@@ -0,0 +1,277 @@
+#[cfg(kernel)]
+use core::arch::{asm, global_asm};
+use core::fmt;
+use core::mem::size_of;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(kernel)]
+use super::gdt::gdt::{KERNEL_CODE_SELECTOR, KERNEL_DATA_SELECTOR};
+#[cfg(kernel)]
+use crate::sync::Lazy;
+
+/// Number of CPU exception vectors; nothing above them is wired yet (PIC, syscalls).
+pub const NUM_EXCEPTIONS: usize = 32;
+
+pub const DEBUG: u8 = 1;
+pub const BREAKPOINT: u8 = 3;
+
+const EXCEPTION_NAMES: [&str; NUM_EXCEPTIONS] = [
+    "divide error",
+    "debug",
+    "non-maskable interrupt",
+    "breakpoint",
+    "overflow",
+    "bound range exceeded",
+    "invalid opcode",
+    "device not available",
+    "double fault",
+    "coprocessor segment overrun",
+    "invalid TSS",
+    "segment not present",
+    "stack-segment fault",
+    "general protection fault",
+    "page fault",
+    "reserved",
+    "x87 floating-point exception",
+    "alignment check",
+    "machine check",
+    "SIMD floating-point exception",
+    "virtualization exception",
+    "control protection exception",
+    "reserved",
+    "reserved",
+    "reserved",
+    "reserved",
+    "reserved",
+    "reserved",
+    "hypervisor injection exception",
+    "VMM communication exception",
+    "security exception",
+    "reserved",
+];
+
+/*
+    Entry stubs. Each one makes the stack look the same whether or not the CPU
+    pushed an error code, then the common part saves the rest and hands a
+    `TrapFrame` to `kfs_trap_dispatch`:
+
+        eflags, cs, eip             pushed by the CPU (ring 0: no esp/ss)
+        error code (or 0), vector   pushed by the stub
+        eax ... edi                 pushad
+        ds, es, fs, gs              lowest address, where the frame starts
+
+    EBP is left alone until the call, so backtraces run on into the
+    interrupted code. Whatever the handler changed in the frame is what the
+    interrupted code resumes with.
+*/
+#[cfg(kernel)]
+global_asm!(
+    ".macro KFS_ISR n, has_error",
+    ".global kfs_isr\\n",
+    "kfs_isr\\n:",
+    ".if \\has_error == 0",
+    "    push 0",
+    ".endif",
+    "    push \\n",
+    "    jmp kfs_isr_common",
+    ".endm",
+    "",
+    ".irp n, 0,1,2,3,4,5,6,7,9,15,16,18,19,20,22,23,24,25,26,27,28,31",
+    "KFS_ISR \\n, 0",
+    ".endr",
+    ".irp n, 8,10,11,12,13,14,17,21,29,30",
+    "KFS_ISR \\n, 1",
+    ".endr",
+    "",
+    "kfs_isr_common:",
+    "    pushad",
+    "    push ds",
+    "    push es",
+    "    push fs",
+    "    push gs",
+    "    mov ax, {data_sel}",
+    "    mov ds, ax",
+    "    mov es, ax",
+    "    push esp",
+    "    cld",
+    "    call kfs_trap_dispatch",
+    "    add esp, 4",
+    "    pop gs",
+    "    pop fs",
+    "    pop es",
+    "    pop ds",
+    "    popad",
+    "    add esp, 8",
+    "    iretd",
+    "",
+    ".section .rodata",
+    ".global kfs_isr_table",
+    ".p2align 2",
+    "kfs_isr_table:",
+    ".irp n, 0,1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20,21,22,23,24,25,26,27,28,29,30,31",
+    "    .long kfs_isr\\n",
+    ".endr",
+    ".text",
+    data_sel = const KERNEL_DATA_SELECTOR,
+);
+
+#[cfg(kernel)]
+extern "C" {
+    static kfs_isr_table: [u32; NUM_EXCEPTIONS];
+}
+
+/// CPU state saved on exception entry, in stack order.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct TrapFrame {
+    pub gs: u32,
+    pub fs: u32,
+    pub es: u32,
+    pub ds: u32,
+    pub edi: u32,
+    pub esi: u32,
+    pub ebp: u32,
+    /// ESP as pushed by `pushad`, inside the frame; see `TrapFrame::esp`.
+    pub esp_dummy: u32,
+    pub ebx: u32,
+    pub edx: u32,
+    pub ecx: u32,
+    pub eax: u32,
+    pub vector: u32,
+    pub error_code: u32,
+    pub eip: u32,
+    pub cs: u32,
+    pub eflags: u32,
+}
+
+impl TrapFrame {
+    /// Stack pointer of the interrupted code, just above the frame (no privilege change).
+    pub fn esp(&self) -> u32 {
+        self as *const Self as u32 + size_of::<Self>() as u32
+    }
+}
+
+impl fmt::Display for TrapFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "EIP={:08x} CS={:04x} EFLAGS={:08x} error={:#x}",
+            self.eip, self.cs, self.eflags, self.error_code
+        )?;
+        writeln!(
+            f,
+            "EAX={:08x} EBX={:08x} ECX={:08x} EDX={:08x}",
+            self.eax, self.ebx, self.ecx, self.edx
+        )?;
+        write!(
+            f,
+            "ESI={:08x} EDI={:08x} EBP={:08x} ESP={:08x}",
+            self.esi,
+            self.edi,
+            self.ebp,
+            self.esp()
+        )
+    }
+}
+
+pub type Handler = fn(&mut TrapFrame);
+
+/// Registered handlers as `fn` addresses; 0 means "panic with the frame".
+static HANDLERS: [AtomicUsize; NUM_EXCEPTIONS] = [const { AtomicUsize::new(0) }; NUM_EXCEPTIONS];
+
+/// Routes exception `vector` to `handler`, replacing any previous one.
+pub fn set_handler(vector: u8, handler: Handler) {
+    HANDLERS[vector as usize].store(handler as usize, Ordering::Release);
+}
+
+/// Back to the default for `vector`: panic.
+pub fn clear_handler(vector: u8) {
+    HANDLERS[vector as usize].store(0, Ordering::Release);
+}
+
+#[no_mangle]
+extern "C" fn kfs_trap_dispatch(frame: &mut TrapFrame) {
+    let vector = frame.vector as usize;
+    match HANDLERS.get(vector).map(|h| h.load(Ordering::Acquire)) {
+        Some(0) | None => panic!(
+            "CPU exception {} ({})\n{}",
+            vector,
+            EXCEPTION_NAMES.get(vector).unwrap_or(&"?"),
+            frame
+        ),
+        Some(addr) => {
+            // Only ever stored from a `Handler` in `set_handler`.
+            let handler = unsafe { core::mem::transmute::<usize, Handler>(addr) };
+            handler(frame)
+        }
+    }
+}
+
+/*
+    Interrupt gate: 32-bit offset split around the selector and attributes.
+    Interrupts stay masked in handlers, and TF is cleared on entry, so a
+    single-stepped instruction does not trap again inside its handler.
+*/
+#[cfg(kernel)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Gate {
+    offset_low: u16,
+    selector: u16,
+    zero: u8,
+    attributes: u8,
+    offset_high: u16,
+}
+
+#[cfg(kernel)]
+const GATE_INTERRUPT_32: u8 = 0x8E;
+
+#[cfg(kernel)]
+impl Gate {
+    const MISSING: Self = Self {
+        offset_low: 0,
+        selector: 0,
+        zero: 0,
+        attributes: 0,
+        offset_high: 0,
+    };
+
+    fn interrupt(handler: u32) -> Self {
+        Self {
+            offset_low: handler as u16,
+            selector: KERNEL_CODE_SELECTOR,
+            zero: 0,
+            attributes: GATE_INTERRUPT_32,
+            offset_high: (handler >> 16) as u16,
+        }
+    }
+}
+
+#[cfg(kernel)]
+static IDT: Lazy<[Gate; 256]> = Lazy::new(|| {
+    let mut idt = [Gate::MISSING; 256];
+    let stubs = unsafe { &kfs_isr_table };
+    for (gate, &stub) in idt.iter_mut().zip(stubs.iter()) {
+        *gate = Gate::interrupt(stub);
+    }
+    idt
+});
+
+#[cfg(kernel)]
+#[repr(C, packed)]
+struct DescriptorTablePointer {
+    limit: u16,
+    base: u32,
+}
+
+/// Loads the IDT with the exception stubs. Interrupts are left as they are.
+#[cfg(kernel)]
+pub fn init() {
+    let idt: &[Gate; 256] = &IDT;
+    let ptr = DescriptorTablePointer {
+        limit: (size_of::<[Gate; 256]>() - 1) as u16,
+        base: idt.as_ptr() as u32,
+    };
+    unsafe { asm!("lidt [{}]", in(reg) &ptr, options(readonly, nostack, preserves_flags)) };
+    crate::info!("{} exception vectors installed", NUM_EXCEPTIONS);
+}
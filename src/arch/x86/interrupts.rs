@@ -0,0 +1,126 @@
+/// EFLAGS.IF: maskable interrupts are enabled.
+const EFLAGS_IF: u32 = 1 << 9;
+
+/*
+    The instructions themselves. Host unit tests have no EFLAGS to play
+    with: IF is a flag there instead (see `arch::x86::mock`), and halting
+    panics.
+*/
+#[cfg(kernel)]
+mod cpu {
+    use core::arch::asm;
+
+    pub fn read_eflags() -> u32 {
+        let flags: u32;
+        unsafe {
+            asm!("pushfd", "pop {0}", out(reg) flags, options(nomem, preserves_flags));
+        }
+        flags
+    }
+
+    pub fn cli() {
+        unsafe { asm!("cli", options(nomem, nostack)) };
+    }
+
+    pub unsafe fn sti() {
+        asm!("sti", options(nomem, nostack));
+    }
+
+    pub fn hlt_forever() -> ! {
+        loop {
+            unsafe { asm!("hlt", options(nomem, nostack)) };
+        }
+    }
+
+    pub unsafe fn sti_hlt_cli() {
+        asm!("sti", "hlt", "cli", options(nomem, nostack));
+    }
+}
+
+#[cfg(not(kernel))]
+mod cpu {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    static IF: AtomicBool = AtomicBool::new(false);
+
+    pub fn read_eflags() -> u32 {
+        if IF.load(Ordering::Relaxed) {
+            super::EFLAGS_IF
+        } else {
+            0
+        }
+    }
+
+    pub fn cli() {
+        IF.store(false, Ordering::Relaxed);
+    }
+
+    pub unsafe fn sti() {
+        IF.store(true, Ordering::Relaxed);
+    }
+
+    pub fn hlt_forever() -> ! {
+        panic!("CPU halted");
+    }
+
+    pub unsafe fn sti_hlt_cli() {}
+}
+
+/// Read the EFLAGS register.
+pub fn read_eflags() -> u32 {
+    cpu::read_eflags()
+}
+
+/// Whether IF is set in a saved EFLAGS value.
+pub fn flags_enabled(flags: u32) -> bool {
+    flags & EFLAGS_IF != 0
+}
+
+pub fn are_enabled() -> bool {
+    flags_enabled(read_eflags())
+}
+
+/// Mask maskable interrupts (`cli`).
+pub fn disable() {
+    cpu::cli();
+}
+
+/// Unmask maskable interrupts (`sti`).
+///
+/// # Safety
+/// An IDT able to handle whatever may fire must be loaded.
+pub unsafe fn enable() {
+    cpu::sti();
+}
+
+/// Disable interrupts and return the previous EFLAGS, for `restore`.
+pub fn save_and_disable() -> u32 {
+    let flags = read_eflags();
+    disable();
+    flags
+}
+
+/// Put IF back the way `save_and_disable` found it.
+pub fn restore(flags: u32) {
+    if flags_enabled(flags) {
+        // IF was set before, so a handler table was already in place.
+        unsafe { enable() };
+    }
+}
+
+/// Stop the CPU for good: interrupts off, then `hlt` (again after any NMI).
+pub fn halt_forever() -> ! {
+    disable();
+    cpu::hlt_forever()
+}
+
+/// Enable interrupts and halt until the next one, then disable them again.
+///
+/// `sti` only takes effect after the following instruction, so an interrupt
+/// arriving in between still wakes the `hlt` instead of being missed.
+///
+/// # Safety
+/// Same as `enable`.
+pub unsafe fn enable_and_wait() {
+    cpu::sti_hlt_cli();
+}
@@ -0,0 +1,134 @@
+/*
+    Memory-mapped I/O access (VGA text and font memory, linear framebuffers).
+
+    Same scheme as `port`: drivers hold `Volatile<T>` handles or use the free
+    functions, which go through the `Mmio` backend chosen at compile time -
+    volatile pointer accesses in the kernel, a sparse fake address space in
+    `arch::x86::mock` for host unit tests, so a driver can "write to 0xB8000"
+    there and be checked.
+*/
+
+use core::marker::PhantomData;
+
+/// Value that can be moved to or from device memory in one access.
+pub trait Word: Copy {
+    const SIZE: usize;
+    fn to_u32(self) -> u32;
+    fn from_u32(v: u32) -> Self;
+}
+
+macro_rules! impl_word {
+    ($($t:ty),*) => {$(
+        impl Word for $t {
+            const SIZE: usize = core::mem::size_of::<$t>();
+            fn to_u32(self) -> u32 {
+                self as u32
+            }
+            fn from_u32(v: u32) -> Self {
+                v as $t
+            }
+        }
+    )*};
+}
+
+impl_word!(u8, u16, u32);
+
+/// Raw device memory accesses at physical (identity-mapped) addresses.
+/// The safety contract is the one of the matching free function below.
+#[allow(clippy::missing_safety_doc)]
+pub trait Mmio {
+    unsafe fn read<T: Word>(addr: usize) -> T;
+    unsafe fn write<T: Word>(addr: usize, val: T);
+    /// `memmove` of `len` bytes within device memory.
+    unsafe fn copy(src: usize, dst: usize, len: usize);
+}
+
+/// Volatile accesses to the real memory bus.
+#[cfg(kernel)]
+pub struct Hardware;
+
+#[cfg(kernel)]
+impl Mmio for Hardware {
+    unsafe fn read<T: Word>(addr: usize) -> T {
+        core::ptr::read_volatile(addr as *const T)
+    }
+
+    unsafe fn write<T: Word>(addr: usize, val: T) {
+        core::ptr::write_volatile(addr as *mut T, val)
+    }
+
+    unsafe fn copy(src: usize, dst: usize, len: usize) {
+        core::ptr::copy(src as *const u8, dst as *mut u8, len)
+    }
+}
+
+#[cfg(kernel)]
+type Backend = Hardware;
+
+#[cfg(not(kernel))]
+type Backend = super::mock::MockMemory;
+
+/// # Safety
+/// `addr` must be mapped device memory that is valid to read as a `T`.
+#[inline(always)]
+pub unsafe fn read<T: Word>(addr: usize) -> T {
+    Backend::read(addr)
+}
+
+/// # Safety
+/// `addr` must be mapped device memory that nothing else is writing to.
+#[inline(always)]
+pub unsafe fn write<T: Word>(addr: usize, val: T) {
+    Backend::write(addr, val)
+}
+
+/// # Safety
+/// Both ranges must be mapped device memory; they may overlap.
+#[inline(always)]
+pub unsafe fn copy(src: usize, dst: usize, len: usize) {
+    Backend::copy(src, dst, len)
+}
+
+/// Device memory holding a `T` (or the first of an array of them).
+///
+/// Accesses are never merged, reordered or elided, and go through the
+/// backend, so the handle works the same against the mock.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Volatile<T: Word> {
+    addr: usize,
+    word: PhantomData<T>,
+}
+
+impl<T: Word> Volatile<T> {
+    /// # Safety
+    /// `addr` must be mapped device memory, aligned for `T`, that nothing
+    /// else writes to while the handle (or one derived from it) is used.
+    pub const unsafe fn new(addr: usize) -> Self {
+        Self {
+            addr,
+            word: PhantomData,
+        }
+    }
+
+    pub const fn addr(self) -> usize {
+        self.addr
+    }
+
+    /// The `T` `count` elements further on.
+    ///
+    /// # Safety
+    /// The result must still be within the device memory given to `new`.
+    pub const unsafe fn add(self, count: usize) -> Self {
+        Self::new(self.addr + count * T::SIZE)
+    }
+
+    #[inline(always)]
+    pub fn read(self) -> T {
+        unsafe { read(self.addr) }
+    }
+
+    #[inline(always)]
+    pub fn write(self, val: T) {
+        unsafe { write(self.addr, val) }
+    }
+}
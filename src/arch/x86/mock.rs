@@ -0,0 +1,131 @@
+/*
+    Stand-ins for the hardware when the crate is built for unit tests on the
+    development machine (`make unit`). Only compiled off the kernel target.
+
+        ports       writes are recorded; reads return queued values, or all
+                    ones (a floating bus) when nothing was queued
+        memory      sparse fake physical address space, zero where untouched
+        EFLAGS.IF   a plain flag behind `interrupts`, off at start as after
+                    a Multiboot handoff
+
+    Tests run one after the other on one thread; each starts with `reset`.
+*/
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::vec::Vec;
+
+use super::mmio::{Mmio, Word};
+use super::port::PortIo;
+
+#[derive(Default)]
+struct State {
+    reads: HashMap<u16, VecDeque<u32>>,
+    writes: Vec<(u16, u32)>,
+    memory: HashMap<usize, u8>,
+}
+
+std::thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::default());
+}
+
+/// Forgets all port traffic and fake memory contents.
+pub fn reset() {
+    STATE.with(|s| *s.borrow_mut() = State::default());
+}
+
+/// Makes the next read of `port` return `value` (truncated to the read width).
+pub fn queue_read(port: u16, value: u32) {
+    STATE.with(|s| {
+        s.borrow_mut()
+            .reads
+            .entry(port)
+            .or_default()
+            .push_back(value)
+    });
+}
+
+/// Port writes since the last call, oldest first, as (port, value).
+pub fn take_writes() -> Vec<(u16, u32)> {
+    STATE.with(|s| core::mem::take(&mut s.borrow_mut().writes))
+}
+
+/// Reads fake device memory without going through a driver.
+pub fn peek<T: Word>(addr: usize) -> T {
+    unsafe { MockMemory::read(addr) }
+}
+
+/// Writes fake device memory without going through a driver.
+pub fn poke<T: Word>(addr: usize, val: T) {
+    unsafe { MockMemory::write(addr, val) }
+}
+
+pub struct MockPorts;
+
+impl MockPorts {
+    fn read(port: u16) -> u32 {
+        STATE.with(|s| {
+            s.borrow_mut()
+                .reads
+                .get_mut(&port)
+                .and_then(VecDeque::pop_front)
+                .unwrap_or(u32::MAX)
+        })
+    }
+
+    fn write(port: u16, val: u32) {
+        STATE.with(|s| s.borrow_mut().writes.push((port, val)));
+    }
+}
+
+impl PortIo for MockPorts {
+    unsafe fn read8(port: u16) -> u8 {
+        Self::read(port) as u8
+    }
+    unsafe fn read16(port: u16) -> u16 {
+        Self::read(port) as u16
+    }
+    unsafe fn read32(port: u16) -> u32 {
+        Self::read(port)
+    }
+    unsafe fn write8(port: u16, val: u8) {
+        Self::write(port, val as u32)
+    }
+    unsafe fn write16(port: u16, val: u16) {
+        Self::write(port, val as u32)
+    }
+    unsafe fn write32(port: u16, val: u32) {
+        Self::write(port, val)
+    }
+}
+
+pub struct MockMemory;
+
+impl Mmio for MockMemory {
+    unsafe fn read<T: Word>(addr: usize) -> T {
+        STATE.with(|s| {
+            let s = s.borrow();
+            let v = (0..T::SIZE).fold(0u32, |v, i| {
+                v | (s.memory.get(&(addr + i)).copied().unwrap_or(0) as u32) << (8 * i)
+            });
+            T::from_u32(v)
+        })
+    }
+
+    unsafe fn write<T: Word>(addr: usize, val: T) {
+        STATE.with(|s| {
+            let mut s = s.borrow_mut();
+            let v = val.to_u32();
+            for i in 0..T::SIZE {
+                s.memory.insert(addr + i, (v >> (8 * i)) as u8);
+            }
+        })
+    }
+
+    unsafe fn copy(src: usize, dst: usize, len: usize) {
+        let bytes: Vec<u8> = (0..len).map(|i| Self::read::<u8>(src + i)).collect();
+        for (i, b) in bytes.into_iter().enumerate() {
+            Self::write(dst + i, b);
+        }
+    }
+}
@@ -1,2 +1,11 @@
+pub mod context;
 pub mod gdt;
+pub mod idt;
+pub mod interrupts;
+pub mod mmio;
+#[cfg(not(kernel))]
+pub mod mock;
 pub mod port;
+#[cfg(kernel)]
+pub mod registers;
+pub mod tsc;
@@ -1,29 +1,396 @@
+/*
+    I/O port access.
+
+    Drivers name their registers with the typed handles `Port<T>`,
+    `PortReadOnly<T>` and `PortWriteOnly<T>` (T = u8, u16 or u32), or call
+    the free functions. Both go through the `PortIo` backend selected at
+    compile time: the `in`/`out` instructions in the kernel, and the recorder
+    in `arch::x86::mock` for host unit tests, which replays queued reads.
+*/
+
+#[cfg(kernel)]
 use core::arch::asm;
+use core::marker::PhantomData;
+
+/// Raw port accesses of each width.
+/// The safety contract is the one of the matching free function below.
+#[allow(clippy::missing_safety_doc)]
+pub trait PortIo {
+    unsafe fn read8(port: u16) -> u8;
+    unsafe fn read16(port: u16) -> u16;
+    unsafe fn read32(port: u16) -> u32;
+    unsafe fn write8(port: u16, val: u8);
+    unsafe fn write16(port: u16, val: u16);
+    unsafe fn write32(port: u16, val: u32);
+
+    /// `rep insw`: fills `buf` from consecutive reads of `port`.
+    unsafe fn read16_string(port: u16, buf: &mut [u16]) {
+        for w in buf {
+            *w = Self::read16(port);
+        }
+    }
+
+    /// `rep outsw`: writes `buf` to `port`, one word after the other.
+    unsafe fn write16_string(port: u16, buf: &[u16]) {
+        for &w in buf {
+            Self::write16(port, w);
+        }
+    }
+}
+
+/// The real `in`/`out` instructions.
+#[cfg(kernel)]
+pub struct Hardware;
+
+#[cfg(kernel)]
+impl PortIo for Hardware {
+    unsafe fn read8(port: u16) -> u8 {
+        let mut v: u8;
+        asm!(
+            "in al, dx",
+            out("al") v,
+            in("dx") port,
+            options(nostack, preserves_flags)
+        );
+        v
+    }
+
+    unsafe fn read16(port: u16) -> u16 {
+        let mut v: u16;
+        asm!(
+            "in ax, dx",
+            out("ax") v,
+            in("dx") port,
+            options(nostack, preserves_flags)
+        );
+        v
+    }
+
+    unsafe fn read32(port: u16) -> u32 {
+        let mut v: u32;
+        asm!(
+            "in eax, dx",
+            out("eax") v,
+            in("dx") port,
+            options(nostack, preserves_flags)
+        );
+        v
+    }
+
+    unsafe fn write8(port: u16, val: u8) {
+        asm!(
+            "out dx, al",
+            in("dx") port,
+            in("al") val,
+            options(nostack, preserves_flags)
+        );
+    }
+
+    unsafe fn write16(port: u16, val: u16) {
+        asm!(
+            "out dx, ax",
+            in("dx") port,
+            in("ax") val,
+            options(nostack, preserves_flags)
+        );
+    }
+
+    unsafe fn write32(port: u16, val: u32) {
+        asm!(
+            "out dx, eax",
+            in("dx") port,
+            in("eax") val,
+            options(nostack, preserves_flags)
+        );
+    }
+
+    unsafe fn read16_string(port: u16, buf: &mut [u16]) {
+        asm!(
+            "rep insw",
+            in("dx") port,
+            inout("edi") buf.as_mut_ptr() => _,
+            inout("ecx") buf.len() => _,
+            options(nostack, preserves_flags)
+        );
+    }
+
+    unsafe fn write16_string(port: u16, buf: &[u16]) {
+        // ESI is reserved by LLVM on i386, so it is swapped in by hand.
+        asm!(
+            "xchg esi, {src}",
+            "rep outsw",
+            "mov esi, {src}",
+            src = inout(reg) buf.as_ptr() => _,
+            in("dx") port,
+            inout("ecx") buf.len() => _,
+            options(readonly, nostack, preserves_flags)
+        );
+    }
+}
+
+#[cfg(kernel)]
+type Backend = Hardware;
+
+#[cfg(not(kernel))]
+type Backend = super::mock::MockPorts;
 
 /// Send an 8-bit value to an I/O port.
 ///
 /// # Safety
 /// Directly accesses hardware. The caller must ensure the port address is valid for the current hardware.
+#[inline(always)]
 pub unsafe fn outb(port: u16, val: u8) {
-    asm!(
-        "out dx, al",
-        in("dx") port,
-        in("al") val,
-        options(nostack, preserves_flags)
-    );
+    Backend::write8(port, val)
 }
 
 /// Read an 8-bit value from an I/O port.
 ///
 /// # Safety
 /// Directly accesses hardware. Reading from the wrong port can cause undefined behavior.
+#[inline(always)]
 pub unsafe fn inb(port: u16) -> u8 {
-    let mut v: u8;
-    asm!(
-        "in al, dx",
-        out("al") v,
-        in("dx") port,
-        options(nostack, preserves_flags)
-    );
-    v
+    Backend::read8(port)
+}
+
+/// Send a 16-bit value to an I/O port.
+///
+/// # Safety
+/// Directly accesses hardware. The caller must ensure the port address is valid for the current hardware.
+#[inline(always)]
+pub unsafe fn outw(port: u16, val: u16) {
+    Backend::write16(port, val)
+}
+
+/// Read a 16-bit value from an I/O port.
+///
+/// # Safety
+/// Directly accesses hardware. Reading from the wrong port can cause undefined behavior.
+#[inline(always)]
+pub unsafe fn inw(port: u16) -> u16 {
+    Backend::read16(port)
+}
+
+/// Send a 32-bit value to an I/O port.
+///
+/// # Safety
+/// Directly accesses hardware. The caller must ensure the port address is valid for the current hardware.
+#[inline(always)]
+pub unsafe fn outl(port: u16, val: u32) {
+    Backend::write32(port, val)
+}
+
+/// Read a 32-bit value from an I/O port.
+///
+/// # Safety
+/// Directly accesses hardware. Reading from the wrong port can cause undefined behavior.
+#[inline(always)]
+pub unsafe fn inl(port: u16) -> u32 {
+    Backend::read32(port)
+}
+
+/// Read a sequence of 16-bit values from an I/O port (`rep insw`).
+///
+/// # Safety
+/// Directly accesses hardware. Every read may consume data from the device.
+#[inline(always)]
+pub unsafe fn insw(port: u16, buf: &mut [u16]) {
+    Backend::read16_string(port, buf)
+}
+
+/// Send a sequence of 16-bit values to an I/O port (`rep outsw`).
+///
+/// # Safety
+/// Directly accesses hardware. The caller must ensure the port address is valid for the current hardware.
+#[inline(always)]
+pub unsafe fn outsw(port: u16, buf: &[u16]) {
+    Backend::write16_string(port, buf)
+}
+
+/// Width of a port access: `u8`, `u16` or `u32`.
+pub trait PortValue: Copy {
+    /// # Safety
+    /// See `inb`.
+    unsafe fn read_from(port: u16) -> Self;
+    /// # Safety
+    /// See `outb`.
+    unsafe fn write_to(port: u16, val: Self);
+}
+
+impl PortValue for u8 {
+    unsafe fn read_from(port: u16) -> Self {
+        inb(port)
+    }
+    unsafe fn write_to(port: u16, val: Self) {
+        outb(port, val)
+    }
+}
+
+impl PortValue for u16 {
+    unsafe fn read_from(port: u16) -> Self {
+        inw(port)
+    }
+    unsafe fn write_to(port: u16, val: Self) {
+        outw(port, val)
+    }
+}
+
+impl PortValue for u32 {
+    unsafe fn read_from(port: u16) -> Self {
+        inl(port)
+    }
+    unsafe fn write_to(port: u16, val: Self) {
+        outl(port, val)
+    }
+}
+
+/// A read/write I/O port accessed `T` at a time.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Port<T: PortValue> {
+    port: u16,
+    width: PhantomData<T>,
+}
+
+/// An I/O port that is only ever read, like a status register.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PortReadOnly<T: PortValue> {
+    port: u16,
+    width: PhantomData<T>,
+}
+
+/// An I/O port that is only ever written, like a command register.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PortWriteOnly<T: PortValue> {
+    port: u16,
+    width: PhantomData<T>,
+}
+
+impl<T: PortValue> Port<T> {
+    pub const fn new(port: u16) -> Self {
+        Self {
+            port,
+            width: PhantomData,
+        }
+    }
+
+    pub const fn number(&self) -> u16 {
+        self.port
+    }
+
+    /// # Safety
+    /// Directly accesses hardware; see `inb`.
+    #[inline(always)]
+    pub unsafe fn read(&self) -> T {
+        T::read_from(self.port)
+    }
+
+    /// # Safety
+    /// Directly accesses hardware; see `outb`.
+    #[inline(always)]
+    pub unsafe fn write(&self, val: T) {
+        T::write_to(self.port, val)
+    }
+}
+
+impl<T: PortValue> PortReadOnly<T> {
+    pub const fn new(port: u16) -> Self {
+        Self {
+            port,
+            width: PhantomData,
+        }
+    }
+
+    pub const fn number(&self) -> u16 {
+        self.port
+    }
+
+    /// # Safety
+    /// Directly accesses hardware; see `inb`.
+    #[inline(always)]
+    pub unsafe fn read(&self) -> T {
+        T::read_from(self.port)
+    }
+}
+
+impl<T: PortValue> PortWriteOnly<T> {
+    pub const fn new(port: u16) -> Self {
+        Self {
+            port,
+            width: PhantomData,
+        }
+    }
+
+    pub const fn number(&self) -> u16 {
+        self.port
+    }
+
+    /// # Safety
+    /// Directly accesses hardware; see `outb`.
+    #[inline(always)]
+    pub unsafe fn write(&self, val: T) {
+        T::write_to(self.port, val)
+    }
+}
+
+impl Port<u16> {
+    /// # Safety
+    /// See `insw`.
+    pub unsafe fn read_string(&self, buf: &mut [u16]) {
+        insw(self.port, buf)
+    }
+
+    /// # Safety
+    /// See `outsw`.
+    pub unsafe fn write_string(&self, buf: &[u16]) {
+        outsw(self.port, buf)
+    }
+}
+
+impl PortReadOnly<u16> {
+    /// # Safety
+    /// See `insw`.
+    pub unsafe fn read_string(&self, buf: &mut [u16]) {
+        insw(self.port, buf)
+    }
+}
+
+impl PortWriteOnly<u16> {
+    /// # Safety
+    /// See `outsw`.
+    pub unsafe fn write_string(&self, buf: &[u16]) {
+        outsw(self.port, buf)
+    }
+}
+
+#[cfg(all(test, not(kernel)))]
+mod tests {
+    use super::*;
+    use crate::arch::x86::mock;
+
+    #[test_case]
+    fn typed_ports_use_their_width() {
+        mock::queue_read(0x60, 0x1234);
+        mock::queue_read(0xCFC, 0x8086_1237);
+        unsafe {
+            Port::<u8>::new(0x80).write(0x34);
+            PortWriteOnly::<u16>::new(0x1CE).write(0xBEEF);
+            assert_eq!(Port::<u8>::new(0x60).read(), 0x34);
+            assert_eq!(PortReadOnly::<u32>::new(0xCFC).read(), 0x8086_1237);
+        }
+        assert_eq!(mock::take_writes(), [(0x80, 0x34), (0x1CE, 0xBEEF)]);
+    }
+
+    #[test_case]
+    fn string_io_moves_one_word_per_access() {
+        let data = Port::<u16>::new(0x1F0);
+        for w in [0xA1, 0xB2, 0xC3] {
+            mock::queue_read(0x1F0, w);
+        }
+        let mut buf = [0u16; 3];
+        unsafe {
+            data.read_string(&mut buf);
+            data.write_string(&buf[1..]);
+        }
+        assert_eq!(buf, [0xA1, 0xB2, 0xC3]);
+        assert_eq!(mock::take_writes(), [(0x1F0, 0xB2), (0x1F0, 0xC3)]);
+    }
 }
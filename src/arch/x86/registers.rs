@@ -0,0 +1,99 @@
+use core::arch::asm;
+use core::fmt;
+
+/// Snapshot of the CPU state, for diagnostics.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Registers {
+    pub eax: u32,
+    pub ebx: u32,
+    pub ecx: u32,
+    pub edx: u32,
+    pub esi: u32,
+    pub edi: u32,
+    pub ebp: u32,
+    pub esp: u32,
+    pub eflags: u32,
+    pub cs: u16,
+    pub ds: u16,
+    pub es: u16,
+    pub fs: u16,
+    pub gs: u16,
+    pub ss: u16,
+    pub cr0: u32,
+    pub cr2: u32,
+    pub cr3: u32,
+    pub cr4: u32,
+}
+
+impl Registers {
+    /*
+        Inlined so the values are those of the caller. The general-purpose
+        registers are stored before anything else runs, but the compiler still
+        had to put `regs` in one of them: that one shows the snapshot's address.
+    */
+    #[inline(always)]
+    pub fn capture() -> Self {
+        let mut r = Self::default();
+        let regs: *mut Self = &mut r;
+        unsafe {
+            asm!(
+                "mov [{r} + {eax}], eax",
+                "mov [{r} + {ebx}], ebx",
+                "mov [{r} + {ecx}], ecx",
+                "mov [{r} + {edx}], edx",
+                "mov [{r} + {esi}], esi",
+                "mov [{r} + {edi}], edi",
+                "mov [{r} + {ebp}], ebp",
+                "mov [{r} + {esp}], esp",
+                r = in(reg) regs,
+                eax = const core::mem::offset_of!(Registers, eax),
+                ebx = const core::mem::offset_of!(Registers, ebx),
+                ecx = const core::mem::offset_of!(Registers, ecx),
+                edx = const core::mem::offset_of!(Registers, edx),
+                esi = const core::mem::offset_of!(Registers, esi),
+                edi = const core::mem::offset_of!(Registers, edi),
+                ebp = const core::mem::offset_of!(Registers, ebp),
+                esp = const core::mem::offset_of!(Registers, esp),
+                options(nostack, preserves_flags),
+            );
+            r.eflags = super::interrupts::read_eflags();
+            asm!("mov {0:x}, cs", out(reg) r.cs, options(nomem, nostack, preserves_flags));
+            asm!("mov {0:x}, ds", out(reg) r.ds, options(nomem, nostack, preserves_flags));
+            asm!("mov {0:x}, es", out(reg) r.es, options(nomem, nostack, preserves_flags));
+            asm!("mov {0:x}, fs", out(reg) r.fs, options(nomem, nostack, preserves_flags));
+            asm!("mov {0:x}, gs", out(reg) r.gs, options(nomem, nostack, preserves_flags));
+            asm!("mov {0:x}, ss", out(reg) r.ss, options(nomem, nostack, preserves_flags));
+            asm!("mov {:e}, cr0", out(reg) r.cr0, options(nomem, nostack, preserves_flags));
+            asm!("mov {:e}, cr2", out(reg) r.cr2, options(nomem, nostack, preserves_flags));
+            asm!("mov {:e}, cr3", out(reg) r.cr3, options(nomem, nostack, preserves_flags));
+            asm!("mov {:e}, cr4", out(reg) r.cr4, options(nomem, nostack, preserves_flags));
+        }
+        r
+    }
+}
+
+/// Four lines: general registers, segments and EFLAGS, control registers.
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "EAX={:08x} EBX={:08x} ECX={:08x} EDX={:08x}",
+            self.eax, self.ebx, self.ecx, self.edx
+        )?;
+        writeln!(
+            f,
+            "ESI={:08x} EDI={:08x} EBP={:08x} ESP={:08x}",
+            self.esi, self.edi, self.ebp, self.esp
+        )?;
+        writeln!(
+            f,
+            "CS={:04x} DS={:04x} ES={:04x} FS={:04x} GS={:04x} SS={:04x} EFLAGS={:08x}",
+            self.cs, self.ds, self.es, self.fs, self.gs, self.ss, self.eflags
+        )?;
+        write!(
+            f,
+            "CR0={:08x} CR2={:08x} CR3={:08x} CR4={:08x}",
+            self.cr0, self.cr2, self.cr3, self.cr4
+        )
+    }
+}
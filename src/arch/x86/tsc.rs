@@ -0,0 +1,51 @@
+/*
+    Time Stamp Counter, the only clock until a timer interrupt exists.
+
+    Its rate is measured once against PIT channel 2, which can be polled
+    without interrupts: the channel counts down from `PIT_CALIBRATION_TICKS`
+    in mode 0 and raises its output (port 0x61 bit 5) when it reaches zero.
+*/
+
+use super::port::{Port, PortWriteOnly};
+
+const PIT_HZ: u64 = 1_193_182;
+/// 10 ms worth of PIT ticks.
+const PIT_CALIBRATION_TICKS: u16 = 11_932;
+
+const PIT_CH2_DATA: Port<u8> = Port::new(0x42);
+const PIT_COMMAND: PortWriteOnly<u8> = PortWriteOnly::new(0x43);
+/// Keyboard controller port B: bit 0 gates channel 2, bit 1 drives the speaker.
+const PORT_B: Port<u8> = Port::new(0x61);
+
+const PORT_B_CH2_GATE: u8 = 1 << 0;
+const PORT_B_SPEAKER: u8 = 1 << 1;
+const PORT_B_CH2_OUT: u8 = 1 << 5;
+/// Channel 2, low byte then high byte, mode 0 (interrupt on terminal count), binary.
+const PIT_CH2_ONESHOT: u8 = 0b1011_0000;
+
+#[inline(always)]
+pub fn read() -> u64 {
+    let (lo, hi): (u32, u32);
+    unsafe {
+        core::arch::asm!("rdtsc", out("eax") lo, out("edx") hi, options(nomem, nostack, preserves_flags));
+    }
+    (hi as u64) << 32 | lo as u64
+}
+
+/// Measures the TSC frequency in Hz, busy-waiting about 10 ms.
+pub fn calibrate_hz() -> u64 {
+    unsafe {
+        let port_b = PORT_B.read();
+        PORT_B.write((port_b & !PORT_B_SPEAKER) | PORT_B_CH2_GATE);
+        PIT_COMMAND.write(PIT_CH2_ONESHOT);
+        PIT_CH2_DATA.write(PIT_CALIBRATION_TICKS as u8);
+        PIT_CH2_DATA.write((PIT_CALIBRATION_TICKS >> 8) as u8);
+        let start = read();
+        while PORT_B.read() & PORT_B_CH2_OUT == 0 {
+            core::hint::spin_loop();
+        }
+        let cycles = read() - start;
+        PORT_B.write(port_b);
+        cycles * PIT_HZ / PIT_CALIBRATION_TICKS as u64
+    }
+}
@@ -2,10 +2,13 @@
 ; Bootloader entry stub for a Multiboot-compliant kernel
 ; =============================================================================
 ; This assembly file provides:
-;   - A valid Multiboot header (recognized by GRUB and other multiboot loaders)
+;   - Valid Multiboot 1 and Multiboot2 headers (recognized by GRUB and other
+;     multiboot loaders), optionally asking for a linear framebuffer (assemble
+;     with -DKFS_FRAMEBUFFER)
 ;   - A simple stack setup (16 KiB)
 ;   - A call into the Rust kernel entry point (_start_kernel)
-;     using the cdecl ABI with (magic, mbi) taken from EAX/EBX
+;     using the cdecl ABI with (magic, mbi) taken from EAX/EBX; the magic
+;     tells Rust which of the two protocols the loader used
 ;   - A safe infinite halt loop after returning
 ; =============================================================================
 
@@ -16,10 +19,27 @@
 ; -----------------------------------------------------------------------------
 MBALIGN   equ 1 << 0                  ; Align modules on page boundaries
 MEMINFO   equ 1 << 1                  ; Request memory map from bootloader
+VIDEO     equ 1 << 2                  ; Request the video mode described below
+%ifdef KFS_FRAMEBUFFER
+MBFLAGS   equ MBALIGN | MEMINFO | VIDEO
+%else
 MBFLAGS   equ MBALIGN | MEMINFO       ; Combine flags
+%endif
 MAGIC     equ 0x1BADB002              ; Required "magic number"
 CHECKSUM  equ -(MAGIC + MBFLAGS)      ; Ensure (magic + flags + checksum) == 0
 
+; Multiboot2 header constants
+MB2_MAGIC equ 0xE85250D6
+MB2_ARCH  equ 0                       ; 32-bit protected mode i386
+MB2_TAG_END         equ 0
+MB2_TAG_FRAMEBUFFER equ 5
+MB2_TAG_OPTIONAL    equ 1             ; The loader may ignore the tag
+
+; Preferred video mode; the loader picks the closest one it can set.
+FB_WIDTH  equ 1024
+FB_HEIGHT equ 768
+FB_DEPTH  equ 32
+
 ; -----------------------------------------------------------------------------
 ; Multiboot header (must be in the first 8 KiB of the kernel binary)
 ; -----------------------------------------------------------------------------
@@ -28,6 +48,37 @@ align 4
     dd MAGIC                          ; Magic number for multiboot compliance
     dd MBFLAGS                        ; Flags requested from the bootloader
     dd CHECKSUM                       ; Ensures validity of header
+    ; Address fields, only read with flag bit 16 (unused: we are ELF)
+    dd 0, 0, 0, 0, 0
+    ; Video fields, only read with the VIDEO flag
+    dd 0                              ; mode_type: 0 = linear graphics
+    dd FB_WIDTH
+    dd FB_HEIGHT
+    dd FB_DEPTH
+
+; -----------------------------------------------------------------------------
+; Multiboot2 header (must be 8-byte aligned, in the first 32 KiB)
+; GRUB uses it for "multiboot2" entries and the one above for "multiboot".
+; -----------------------------------------------------------------------------
+align 8
+mb2_header_start:
+    dd MB2_MAGIC
+    dd MB2_ARCH
+    dd mb2_header_end - mb2_header_start
+    ; Checksum, written so that the 32-bit sum does not overflow in NASM
+    dd 0x100000000 - (MB2_MAGIC + MB2_ARCH + (mb2_header_end - mb2_header_start))
+%ifdef KFS_FRAMEBUFFER
+align 8
+    dw MB2_TAG_FRAMEBUFFER, MB2_TAG_OPTIONAL
+    dd 20                             ; Tag size
+    dd FB_WIDTH
+    dd FB_HEIGHT
+    dd FB_DEPTH
+%endif
+align 8
+    dw MB2_TAG_END, 0
+    dd 8
+mb2_header_end:
 
 ; -----------------------------------------------------------------------------
 ; Uninitialized data section (.bss)
@@ -50,10 +101,12 @@ global _start
 _start:
     ; Initialize stack pointer (ESP) to the top of our reserved stack
     mov esp, stack_top
+    ; Zero EBP so that backtraces know where the frame chain ends
+    xor ebp, ebp
 
     ; GRUB provides:
-    ;   EAX = 0x2BADB002 (Multiboot magic)
-    ;   EBX = pointer to multiboot_info structure
+    ;   EAX = 0x2BADB002 (Multiboot) or 0x36D76289 (Multiboot2) magic
+    ;   EBX = pointer to the matching boot information structure
     ;
     ; Pass them to Rust (cdecl): push last arg first
     extern _start_kernel
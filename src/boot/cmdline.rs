@@ -0,0 +1,291 @@
+/*
+    Kernel command line, as passed by the bootloader:
+
+        loglevel=debug console=serial,vga keymap=fr vga.mode=80x50 quiet
+
+    Arguments are separated by spaces. Each one is a bare flag (`quiet`) or
+    `key=value`; double quotes keep spaces in a value (`key="a b"`, or the
+    whole `"key=a b"`) and are removed. `-` and `_` are the same in keys.
+    Parsing stops at `--`, like Linux, where the rest would belong to init.
+    A leading argument starting with `/` is the kernel path GRUB puts first.
+
+    Subsystems declare the parameters they understand next to the code they
+    configure, much like Linux's `__setup`:
+
+        kernel_param!("loglevel", |level: Level| {
+            set_level(Some(level));
+            Ok(())
+        });
+
+    The value is converted through `ParamValue` before the handler runs, so
+    handlers only see well-typed input. Every declaration is a `Param` placed
+    in the `kfs_params` link section, which `params` walks. `apply` runs the
+    handler of each argument and warns about unknown or invalid ones.
+*/
+
+use core::fmt;
+
+use crate::sync::OnceCell;
+use crate::warn;
+
+/// One `key[=value]` argument.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Arg<'a> {
+    pub key: &'a str,
+    pub value: Option<&'a str>,
+}
+
+/// Iterator over the arguments of a command line.
+pub struct Args<'a> {
+    rest: &'a str,
+}
+
+pub fn args(cmdline: &str) -> Args<'_> {
+    Args { rest: cmdline }
+}
+
+fn unquote(s: &str) -> &str {
+    let s = s.strip_prefix('"').unwrap_or(s);
+    s.strip_suffix('"').unwrap_or(s)
+}
+
+impl<'a> Iterator for Args<'a> {
+    type Item = Arg<'a>;
+
+    fn next(&mut self) -> Option<Arg<'a>> {
+        let s = self.rest.trim_start_matches([' ', '\t', '\n']);
+        let mut quoted = false;
+        let end = s
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    quoted = !quoted;
+                }
+                !quoted && matches!(c, ' ' | '\t' | '\n')
+            })
+            .map_or(s.len(), |(i, _)| i);
+        let (token, rest) = s.split_at(end);
+        self.rest = rest;
+        if token.is_empty() || token == "--" {
+            self.rest = "";
+            return None;
+        }
+        let token = unquote(token);
+        Some(match token.split_once('=') {
+            Some((key, value)) => Arg {
+                key,
+                value: Some(unquote(value)),
+            },
+            None => Arg {
+                key: token,
+                value: None,
+            },
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ParamError {
+    /// `key` alone where `key=value` is needed.
+    MissingValue,
+    /// The value could not be used; says what was expected instead.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParamError::MissingValue => f.write_str("needs a value"),
+            ParamError::Invalid(expected) => write!(f, "expected {}", expected),
+        }
+    }
+}
+
+/// Conversion of a parameter value (`None` for a bare flag) to a handler argument.
+pub trait ParamValue: Sized {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError>;
+}
+
+/// The value itself; the command line lives as long as the kernel.
+impl ParamValue for &'static str {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError> {
+        value.ok_or(ParamError::MissingValue)
+    }
+}
+
+/// A bare flag is `true`; a value must be one of 1/0, y/n, yes/no, on/off, true/false.
+impl ParamValue for bool {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError> {
+        let Some(v) = value else {
+            return Ok(true);
+        };
+        const TRUE: [&str; 5] = ["1", "y", "yes", "on", "true"];
+        const FALSE: [&str; 5] = ["0", "n", "no", "off", "false"];
+        if TRUE.iter().any(|t| t.eq_ignore_ascii_case(v)) {
+            Ok(true)
+        } else if FALSE.iter().any(|f| f.eq_ignore_ascii_case(v)) {
+            Ok(false)
+        } else {
+            Err(ParamError::Invalid("a boolean"))
+        }
+    }
+}
+
+/// Decimal, or hexadecimal with `0x`.
+impl ParamValue for u32 {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError> {
+        let v = value.ok_or(ParamError::MissingValue)?;
+        match v.strip_prefix("0x").or_else(|| v.strip_prefix("0X")) {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => v.parse(),
+        }
+        .map_err(|_| ParamError::Invalid("a number"))
+    }
+}
+
+/// A parameter declared with `kernel_param!`.
+pub struct Param {
+    pub name: &'static str,
+    pub setup: fn(Option<&'static str>) -> Result<(), ParamError>,
+}
+
+// Bounds of the `kfs_params` section, from the linker; only addresses matter.
+extern "C" {
+    static __start_kfs_params: u8;
+    static __stop_kfs_params: u8;
+}
+
+/// Every declared parameter, in link order.
+pub fn params() -> &'static [Param] {
+    unsafe {
+        let start = core::ptr::addr_of!(__start_kfs_params) as *const Param;
+        let end = core::ptr::addr_of!(__stop_kfs_params) as *const Param;
+        // Only `Param`s go in the section, back to back.
+        core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
+
+/// Parameter names match with `-` and `_` interchangeable.
+fn same_key(a: &str, b: &str) -> bool {
+    let norm = |c: u8| if c == b'-' { b'_' } else { c };
+    a.len() == b.len() && a.bytes().zip(b.bytes()).all(|(x, y)| norm(x) == norm(y))
+}
+
+pub fn find(key: &str) -> Option<&'static Param> {
+    params().iter().find(|p| same_key(p.name, key))
+}
+
+/// Declares a boot parameter: `kernel_param!("name", |value: Type| { ...; Ok(()) })`.
+#[macro_export]
+macro_rules! kernel_param {
+    ($name:literal, |$value:ident : $ty:ty| $body:expr) => {
+        const _: () = {
+            fn setup(value: Option<&'static str>) -> Result<(), $crate::boot::cmdline::ParamError> {
+                let $value = <$ty as $crate::boot::cmdline::ParamValue>::parse(value)?;
+                $body
+            }
+
+            #[used]
+            #[link_section = "kfs_params"]
+            static PARAM: $crate::boot::cmdline::Param =
+                $crate::boot::cmdline::Param { name: $name, setup };
+        };
+    };
+}
+
+static CMDLINE: OnceCell<&'static str> = OnceCell::new();
+
+/// The command line given to `apply`, or "" before that.
+pub fn get() -> &'static str {
+    CMDLINE.get().copied().unwrap_or("")
+}
+
+/// Runs the handler of every argument of `cmdline`, once per boot.
+pub fn apply(cmdline: &'static str) {
+    if CMDLINE.set(cmdline).is_err() {
+        return;
+    }
+    crate::info!("command line: {}", cmdline);
+    // GRUB passes the kernel image path first.
+    let mut args = args(cmdline).peekable();
+    args.next_if(|arg| arg.key.starts_with('/'));
+    for arg in args {
+        match find(arg.key) {
+            Some(param) => {
+                if let Err(e) = (param.setup)(arg.value) {
+                    warn!("{}: {}", arg.key, e);
+                }
+            }
+            None => warn!("unknown parameter \"{}\"", arg.key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed<const N: usize>(cmdline: &str) -> [Option<Arg<'_>>; N] {
+        let mut out = [None; N];
+        for (slot, arg) in out.iter_mut().zip(args(cmdline)) {
+            *slot = Some(arg);
+        }
+        out
+    }
+
+    fn arg<'a>(key: &'a str, value: Option<&'a str>) -> Option<Arg<'a>> {
+        Some(Arg { key, value })
+    }
+
+    #[test_case]
+    fn flags_and_values_split_on_spaces() {
+        assert_eq!(
+            parsed::<4>("  quiet loglevel=7\tconsole=serial,vga "),
+            [
+                arg("quiet", None),
+                arg("loglevel", Some("7")),
+                arg("console", Some("serial,vga")),
+                None
+            ]
+        );
+    }
+
+    #[test_case]
+    fn quotes_keep_spaces_and_are_removed() {
+        assert_eq!(
+            parsed::<4>("a=\"x y\" \"b=1 2\" c= d=e=f"),
+            [
+                arg("a", Some("x y")),
+                arg("b", Some("1 2")),
+                arg("c", Some("")),
+                arg("d", Some("e=f"))
+            ]
+        );
+    }
+
+    #[test_case]
+    fn double_dash_ends_kernel_arguments() {
+        assert_eq!(parsed::<3>("a -- init=1"), [arg("a", None), None, None]);
+    }
+
+    #[test_case]
+    fn values_convert_to_their_parameter_type() {
+        assert_eq!(<bool as ParamValue>::parse(None), Ok(true));
+        assert_eq!(<bool as ParamValue>::parse(Some("Off")), Ok(false));
+        assert!(<bool as ParamValue>::parse(Some("maybe")).is_err());
+        assert_eq!(<u32 as ParamValue>::parse(Some("0x3f8")), Ok(0x3F8));
+        assert_eq!(<u32 as ParamValue>::parse(Some("42")), Ok(42));
+        assert_eq!(
+            <u32 as ParamValue>::parse(None),
+            Err(ParamError::MissingValue)
+        );
+    }
+
+    #[test_case]
+    fn declared_parameters_are_found_by_name() {
+        assert!(find("loglevel").is_some());
+        assert!(same_key("vga.mode", "vga.mode"));
+        assert!(same_key("log_level", "log-level"));
+        assert!(find("no-such-parameter").is_none());
+    }
+}
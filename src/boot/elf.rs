@@ -0,0 +1,222 @@
+/*
+    The kernel's own ELF section headers, as passed by the loader (Multiboot
+    `syms` with flag bit 5, or the Multiboot2 ELF sections tag).
+
+    GRUB loads every section, not only the allocated ones, and rewrites the
+    `sh_addr` of those the image did not place itself, so `.symtab` and its
+    `.strtab` are in memory when the kernel was linked with them (see
+    KEEP_SYMBOLS in the Makefile). Like multiboot2.rs, everything is read
+    from byte slices; only `SectionTable::symbols` touches memory.
+*/
+
+/// Elf32_Shdr, the only size this parser reads.
+const SHDR_SIZE: usize = 40;
+/// Elf32_Sym.
+const SYM_SIZE: usize = 16;
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const STT_FUNC: u8 = 2;
+
+fn u32_at(b: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        b.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// The NUL-terminated UTF-8 string at `offset` in a string table.
+fn str_at(strings: &[u8], offset: usize) -> Option<&str> {
+    let s = strings.get(offset..)?;
+    core::str::from_utf8(&s[..s.iter().position(|&c| c == 0)?]).ok()
+}
+
+/// The fields of a section header used here.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Section {
+    pub name: u32,
+    pub kind: u32,
+    pub addr: u32,
+    pub size: u32,
+    /// For a symbol table, the index of its string table.
+    pub link: u32,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SectionTable<'a> {
+    headers: &'a [u8],
+    entsize: usize,
+    /// Index of the section holding the section names.
+    shndx: u32,
+}
+
+impl<'a> SectionTable<'a> {
+    /// `headers` holds `num` headers of `entsize` bytes; `None` if it is
+    /// shorter or the entries are too small to be Elf32_Shdrs.
+    pub fn new(headers: &'a [u8], num: u32, entsize: u32, shndx: u32) -> Option<Self> {
+        let entsize = entsize as usize;
+        if entsize < SHDR_SIZE {
+            return None;
+        }
+        Some(Self {
+            headers: headers.get(..(num as usize).checked_mul(entsize)?)?,
+            entsize,
+            shndx,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.headers.len() / self.entsize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: u32) -> Option<Section> {
+        let start = (index as usize).checked_mul(self.entsize)?;
+        let h = self.headers.get(start..start + SHDR_SIZE)?;
+        Some(Section {
+            name: u32_at(h, 0)?,
+            kind: u32_at(h, 4)?,
+            addr: u32_at(h, 12)?,
+            size: u32_at(h, 20)?,
+            link: u32_at(h, 24)?,
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Section> + '_ {
+        (0..self.len() as u32).filter_map(|i| self.get(i))
+    }
+
+    /// The section holding the section names.
+    pub fn names(&self) -> Option<Section> {
+        self.get(self.shndx)
+    }
+
+    /// The symbol table and the string table it names, when both are there.
+    pub fn find_symtab(&self) -> Option<(Section, Section)> {
+        let symtab = self.iter().find(|s| s.kind == SHT_SYMTAB && s.addr != 0)?;
+        let strtab = self.get(symtab.link)?;
+        (strtab.kind == SHT_STRTAB && strtab.addr != 0).then_some((symtab, strtab))
+    }
+
+    /// The kernel's symbols, read from where the loader put them.
+    ///
+    /// # Safety
+    /// The table must describe the running kernel, with every section the
+    /// loader reported still mapped at its `addr` and never written to.
+    pub unsafe fn symbols(&self) -> Option<SymbolTable<'static>> {
+        let (symtab, strtab) = self.find_symtab()?;
+        let bytes = |s: Section| core::slice::from_raw_parts(s.addr as *const u8, s.size as usize);
+        Some(SymbolTable::new(bytes(symtab), bytes(strtab)))
+    }
+}
+
+/// An ELF symbol table with its string table.
+#[derive(Copy, Clone, Debug)]
+pub struct SymbolTable<'a> {
+    symbols: &'a [u8],
+    strings: &'a [u8],
+}
+
+impl<'a> SymbolTable<'a> {
+    pub fn new(symbols: &'a [u8], strings: &'a [u8]) -> Self {
+        Self { symbols, strings }
+    }
+
+    /// Number of entries, the null symbol included.
+    pub fn len(&self) -> usize {
+        self.symbols.len() / SYM_SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The function containing `addr`, with the offset of `addr` into it.
+    ///
+    /// The table is not sorted: this is a linear scan, fine for diagnostics.
+    /// Functions of unknown size match every address up to the next one.
+    pub fn lookup(&self, addr: usize) -> Option<(&'a str, usize)> {
+        let mut best: Option<(usize, u32)> = None;
+        for sym in self.symbols.chunks_exact(SYM_SIZE) {
+            let (Some(name), Some(value), Some(size)) =
+                (u32_at(sym, 0), u32_at(sym, 4), u32_at(sym, 8))
+            else {
+                continue;
+            };
+            let value = value as usize;
+            let inside = addr >= value && (size == 0 || addr - value < size as usize);
+            if sym[12] & 0xF == STT_FUNC && inside && best.is_none_or(|(v, _)| value > v) {
+                best = Some((value, name));
+            }
+        }
+        let (value, name) = best?;
+        Some((str_at(self.strings, name as usize)?, addr - value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shdr(name: u32, kind: u32, addr: u32, size: u32, link: u32) -> [u8; SHDR_SIZE] {
+        let mut h = [0; SHDR_SIZE];
+        for (offset, v) in [(0, name), (4, kind), (12, addr), (20, size), (24, link)] {
+            h[offset..offset + 4].copy_from_slice(&v.to_le_bytes());
+        }
+        h
+    }
+
+    fn sym(name: u32, value: u32, size: u32, info: u8) -> [u8; SYM_SIZE] {
+        let mut s = [0; SYM_SIZE];
+        s[..4].copy_from_slice(&name.to_le_bytes());
+        s[4..8].copy_from_slice(&value.to_le_bytes());
+        s[8..12].copy_from_slice(&size.to_le_bytes());
+        s[12] = info;
+        s
+    }
+
+    #[test_case]
+    fn symtab_is_found_through_its_link() {
+        let mut headers = [0u8; 4 * SHDR_SIZE];
+        let sections = [
+            shdr(0, 0, 0, 0, 0),
+            shdr(1, SHT_STRTAB, 0x30_0000, 0x100, 0),
+            shdr(9, SHT_SYMTAB, 0x31_0000, 0x200, 1),
+            shdr(17, SHT_STRTAB, 0x32_0000, 0x40, 0),
+        ];
+        for (chunk, s) in headers.chunks_exact_mut(SHDR_SIZE).zip(sections) {
+            chunk.copy_from_slice(&s);
+        }
+        let table = SectionTable::new(&headers, 4, SHDR_SIZE as u32, 3).unwrap();
+        assert_eq!(table.len(), 4);
+        assert_eq!(table.names().map(|s| s.name), Some(17));
+        let (symtab, strtab) = table.find_symtab().unwrap();
+        assert_eq!((symtab.addr, strtab.addr), (0x31_0000, 0x30_0000));
+        // Fewer bytes than the headers announced.
+        assert!(SectionTable::new(&headers, 5, SHDR_SIZE as u32, 3).is_none());
+    }
+
+    #[test_case]
+    fn lookup_picks_the_enclosing_function() {
+        let strings = b"\0kmain\0helper\0data\0";
+        let mut symbols = [0u8; 4 * SYM_SIZE];
+        let entries = [
+            sym(0, 0, 0, 0),
+            sym(1, 0x20_1000, 0x80, STT_FUNC),
+            sym(7, 0x20_1080, 0x10, STT_FUNC),
+            // An object inside the same range never names code.
+            sym(14, 0x20_1084, 4, 1),
+        ];
+        for (chunk, s) in symbols.chunks_exact_mut(SYM_SIZE).zip(entries) {
+            chunk.copy_from_slice(&s);
+        }
+        let table = SymbolTable::new(&symbols, strings);
+        assert_eq!(table.lookup(0x20_1000), Some(("kmain", 0)));
+        assert_eq!(table.lookup(0x20_107F), Some(("kmain", 0x7F)));
+        assert_eq!(table.lookup(0x20_1086), Some(("helper", 6)));
+        assert_eq!(table.lookup(0x20_1090), None);
+        assert_eq!(table.lookup(0x10_0000), None);
+    }
+}
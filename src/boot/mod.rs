@@ -0,0 +1,57 @@
+pub mod cmdline;
+pub mod elf;
+pub mod multiboot;
+pub mod multiboot2;
+
+use crate::drivers::video::framebuffer::Mode;
+use elf::SectionTable;
+
+/// What the loader told us, whichever Multiboot version it spoke.
+#[derive(Copy, Clone)]
+pub enum BootInfo {
+    Multiboot(&'static multiboot::Info),
+    Multiboot2(multiboot2::Info<'static>),
+}
+
+impl BootInfo {
+    /// Picks the protocol from the EAX magic; `None` if neither matches.
+    ///
+    /// # Safety
+    /// `mbi_addr` must be the EBX value passed in by the bootloader, still mapped.
+    pub unsafe fn new(magic: u32, mbi_addr: u32) -> Option<Self> {
+        match magic {
+            multiboot::BOOTLOADER_MAGIC => multiboot::info(magic, mbi_addr).map(Self::Multiboot),
+            multiboot2::BOOTLOADER_MAGIC => multiboot2::info(magic, mbi_addr).map(Self::Multiboot2),
+            _ => None,
+        }
+    }
+
+    pub fn protocol(&self) -> &'static str {
+        match self {
+            BootInfo::Multiboot(_) => "Multiboot",
+            BootInfo::Multiboot2(_) => "Multiboot2",
+        }
+    }
+
+    pub fn cmdline(&self) -> Option<&'static str> {
+        match self {
+            BootInfo::Multiboot(info) => info.cmdline(),
+            BootInfo::Multiboot2(info) => info.cmdline(),
+        }
+    }
+
+    pub fn framebuffer(&self) -> Option<Mode> {
+        match self {
+            BootInfo::Multiboot(info) => info.framebuffer(),
+            BootInfo::Multiboot2(info) => info.framebuffer(),
+        }
+    }
+
+    /// The kernel's ELF section headers, for its own symbols.
+    pub fn elf_sections(&self) -> Option<SectionTable<'static>> {
+        match self {
+            BootInfo::Multiboot(info) => info.elf_sections(),
+            BootInfo::Multiboot2(info) => info.elf_sections()?.table(),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+/*
+    Multiboot (version 1) boot information, handed over by GRUB in EBX.
+
+    Layout per the Multiboot 0.6.96 specification, section 3.3. Each group of
+    fields is only meaningful when its bit is set in `flags`.
+*/
+
+use super::elf::SectionTable;
+use crate::drivers::video::framebuffer::{Mode, PixelFormat};
+
+/// Value found in EAX when the kernel was loaded by a Multiboot loader.
+pub const BOOTLOADER_MAGIC: u32 = 0x2BADB002;
+
+/// `flags` bit: `cmdline` is valid.
+const INFO_CMDLINE: u32 = 1 << 2;
+/// `flags` bit: `syms` is the ELF section header table (num, size, addr, shndx).
+const INFO_ELF_SHDR: u32 = 1 << 5;
+/// `flags` bit: the framebuffer_* fields are valid.
+const INFO_FRAMEBUFFER: u32 = 1 << 12;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FramebufferType {
+    Indexed,
+    Rgb,
+    /// Plain VGA text mode; the framebuffer address is 0xB8000.
+    EgaText,
+    Unknown(u8),
+}
+
+#[repr(C, packed)]
+pub struct Info {
+    pub flags: u32,
+    pub mem_lower: u32,
+    pub mem_upper: u32,
+    pub boot_device: u32,
+    pub cmdline: u32,
+    pub mods_count: u32,
+    pub mods_addr: u32,
+    pub syms: [u32; 4],
+    pub mmap_length: u32,
+    pub mmap_addr: u32,
+    pub drives_length: u32,
+    pub drives_addr: u32,
+    pub config_table: u32,
+    pub boot_loader_name: u32,
+    pub apm_table: u32,
+    pub vbe_control_info: u32,
+    pub vbe_mode_info: u32,
+    pub vbe_mode: u16,
+    pub vbe_interface_seg: u16,
+    pub vbe_interface_off: u16,
+    pub vbe_interface_len: u16,
+    pub framebuffer_addr: u64,
+    pub framebuffer_pitch: u32,
+    pub framebuffer_width: u32,
+    pub framebuffer_height: u32,
+    pub framebuffer_bpp: u8,
+    pub framebuffer_type: u8,
+    // For RGB: red position/size, green position/size, blue position/size.
+    pub color_info: [u8; 6],
+}
+
+/// Returns the boot information if `magic` says a Multiboot loader started us.
+///
+/// # Safety
+/// `mbi_addr` must be the EBX value passed in by the bootloader, still mapped.
+pub unsafe fn info(magic: u32, mbi_addr: u32) -> Option<&'static Info> {
+    if magic != BOOTLOADER_MAGIC || mbi_addr == 0 {
+        return None;
+    }
+    Some(&*(mbi_addr as *const Info))
+}
+
+impl Info {
+    /// The kernel command line, if the loader passed one and it is UTF-8.
+    pub fn cmdline(&self) -> Option<&'static str> {
+        if self.flags & INFO_CMDLINE == 0 || self.cmdline == 0 {
+            return None;
+        }
+        // A NUL-terminated string the loader left in memory we never reuse.
+        let s = unsafe { core::ffi::CStr::from_ptr(self.cmdline as *const core::ffi::c_char) };
+        s.to_str().ok()
+    }
+
+    /// The kernel's section headers, as loaded by the loader.
+    pub fn elf_sections(&self) -> Option<SectionTable<'static>> {
+        if self.flags & INFO_ELF_SHDR == 0 {
+            return None;
+        }
+        let [num, size, addr, shndx] = self.syms;
+        if addr == 0 {
+            return None;
+        }
+        // The loader keeps the table in memory we never reuse.
+        let headers = unsafe {
+            core::slice::from_raw_parts(addr as *const u8, num.saturating_mul(size) as usize)
+        };
+        SectionTable::new(headers, num, size, shndx)
+    }
+
+    pub fn framebuffer_type(&self) -> Option<FramebufferType> {
+        if self.flags & INFO_FRAMEBUFFER == 0 {
+            return None;
+        }
+        Some(match self.framebuffer_type {
+            0 => FramebufferType::Indexed,
+            1 => FramebufferType::Rgb,
+            2 => FramebufferType::EgaText,
+            t => FramebufferType::Unknown(t),
+        })
+    }
+
+    /// The linear framebuffer set up by the loader, if it is a direct-color one
+    /// reachable from 32-bit code.
+    pub fn framebuffer(&self) -> Option<Mode> {
+        if self.framebuffer_type() != Some(FramebufferType::Rgb) {
+            return None;
+        }
+        let addr = self.framebuffer_addr;
+        let base = usize::try_from(addr).ok()?;
+        let c = self.color_info;
+        Some(Mode {
+            base,
+            width: self.framebuffer_width as usize,
+            height: self.framebuffer_height as usize,
+            pitch: self.framebuffer_pitch as usize,
+            bpp: self.framebuffer_bpp,
+            format: PixelFormat {
+                red_pos: c[0],
+                red_size: c[1],
+                green_pos: c[2],
+                green_size: c[3],
+                blue_pos: c[4],
+                blue_size: c[5],
+            },
+        })
+    }
+}
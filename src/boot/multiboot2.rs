@@ -0,0 +1,507 @@
+/*
+    Multiboot2 boot information, handed over by GRUB in EBX.
+
+    The structure is a `total_size`, a reserved word and a list of tags,
+    each `type`, `size` and a payload, padded to 8 bytes and ended by a tag
+    of type 0. It is parsed from a byte slice, so nothing here depends on
+    the fields being aligned and the parser runs in host unit tests.
+
+    Where the specification and GRUB disagree on a layout (framebuffer
+    `reserved`, ELF sections counts), GRUB's multiboot2.h wins: it is what
+    actually writes the structure.
+*/
+
+use super::elf::SectionTable;
+use super::multiboot::FramebufferType;
+use crate::drivers::video::framebuffer::{Mode, PixelFormat};
+
+/// Value found in EAX when the kernel was loaded by a Multiboot2 loader.
+pub const BOOTLOADER_MAGIC: u32 = 0x36D76289;
+
+const TAG_END: u32 = 0;
+const TAG_CMDLINE: u32 = 1;
+const TAG_BOOT_LOADER_NAME: u32 = 2;
+const TAG_MODULE: u32 = 3;
+const TAG_BASIC_MEMINFO: u32 = 4;
+const TAG_MMAP: u32 = 6;
+const TAG_FRAMEBUFFER: u32 = 8;
+const TAG_ELF_SECTIONS: u32 = 9;
+const TAG_ACPI_OLD: u32 = 14;
+const TAG_ACPI_NEW: u32 = 15;
+
+/// Header of the whole structure and of every tag.
+const HEADER_SIZE: usize = 8;
+
+fn u32_at(b: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        b.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn u64_at(b: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        b.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// The NUL-terminated UTF-8 string at the start of `b`.
+fn str_at(b: &[u8]) -> Option<&str> {
+    let len = b.iter().position(|&c| c == 0)?;
+    core::str::from_utf8(&b[..len]).ok()
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Module<'a> {
+    pub start: u32,
+    /// One past the last byte.
+    pub end: u32,
+    pub cmdline: &'a str,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RegionKind {
+    Available,
+    Reserved,
+    AcpiReclaimable,
+    AcpiNvs,
+    Defective,
+    Other(u32),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MemoryRegion {
+    pub base: u64,
+    pub len: u64,
+    pub kind: RegionKind,
+}
+
+/// The BIOS (e820) memory map.
+#[derive(Copy, Clone, Debug)]
+pub struct MemoryMap<'a> {
+    entry_size: usize,
+    entries: &'a [u8],
+}
+
+impl<'a> MemoryMap<'a> {
+    pub fn regions(&self) -> impl Iterator<Item = MemoryRegion> + 'a {
+        // The loader may use larger entries than the 24 bytes read here.
+        let entry_size = self.entry_size.max(24);
+        self.entries.chunks_exact(entry_size).map(|e| MemoryRegion {
+            base: u64_at(e, 0).unwrap_or(0),
+            len: u64_at(e, 8).unwrap_or(0),
+            kind: match u32_at(e, 16).unwrap_or(0) {
+                1 => RegionKind::Available,
+                2 => RegionKind::Reserved,
+                3 => RegionKind::AcpiReclaimable,
+                4 => RegionKind::AcpiNvs,
+                5 => RegionKind::Defective,
+                k => RegionKind::Other(k),
+            },
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FramebufferInfo {
+    pub addr: u64,
+    pub pitch: u32,
+    pub width: u32,
+    pub height: u32,
+    pub bpp: u8,
+    pub kind: FramebufferType,
+    /// For RGB: red position/size, green position/size, blue position/size.
+    pub color_info: [u8; 6],
+}
+
+impl FramebufferInfo {
+    /// The framebuffer as a `Mode`, if it is a direct-color one reachable
+    /// from 32-bit code.
+    pub fn mode(&self) -> Option<Mode> {
+        if self.kind != FramebufferType::Rgb {
+            return None;
+        }
+        let c = self.color_info;
+        Some(Mode {
+            base: usize::try_from(self.addr).ok()?,
+            width: self.width as usize,
+            height: self.height as usize,
+            pitch: self.pitch as usize,
+            bpp: self.bpp,
+            format: PixelFormat {
+                red_pos: c[0],
+                red_size: c[1],
+                green_pos: c[2],
+                green_size: c[3],
+                blue_pos: c[4],
+                blue_size: c[5],
+            },
+        })
+    }
+}
+
+/// Section header table of the loaded kernel image.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ElfSections<'a> {
+    pub num: u32,
+    pub entsize: u32,
+    /// Index of the section holding the section names.
+    pub shndx: u32,
+    pub headers: &'a [u8],
+}
+
+impl<'a> ElfSections<'a> {
+    pub fn table(&self) -> Option<SectionTable<'a>> {
+        SectionTable::new(self.headers, self.num, self.entsize, self.shndx)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Tag<'a> {
+    Cmdline(&'a str),
+    BootLoaderName(&'a str),
+    Module(Module<'a>),
+    /// KiB of memory below 1 MiB and above 1 MiB.
+    BasicMeminfo {
+        mem_lower: u32,
+        mem_upper: u32,
+    },
+    MemoryMap(MemoryMap<'a>),
+    Framebuffer(FramebufferInfo),
+    ElfSections(ElfSections<'a>),
+    /// Copy of the ACPI 1.0 RSDP.
+    AcpiOldRsdp(&'a [u8]),
+    /// Copy of the ACPI 2.0+ XSDP.
+    AcpiNewRsdp(&'a [u8]),
+    /// A tag this parser does not decode, or one too short for its type.
+    Other {
+        kind: u32,
+        payload: &'a [u8],
+    },
+}
+
+impl<'a> Tag<'a> {
+    fn parse(kind: u32, p: &'a [u8]) -> Option<Self> {
+        Some(match kind {
+            TAG_CMDLINE => Tag::Cmdline(str_at(p)?),
+            TAG_BOOT_LOADER_NAME => Tag::BootLoaderName(str_at(p)?),
+            TAG_MODULE => Tag::Module(Module {
+                start: u32_at(p, 0)?,
+                end: u32_at(p, 4)?,
+                cmdline: str_at(p.get(8..)?)?,
+            }),
+            TAG_BASIC_MEMINFO => Tag::BasicMeminfo {
+                mem_lower: u32_at(p, 0)?,
+                mem_upper: u32_at(p, 4)?,
+            },
+            TAG_MMAP => Tag::MemoryMap(MemoryMap {
+                entry_size: u32_at(p, 0)? as usize,
+                entries: p.get(8..)?,
+            }),
+            TAG_FRAMEBUFFER => Tag::Framebuffer(FramebufferInfo {
+                addr: u64_at(p, 0)?,
+                pitch: u32_at(p, 8)?,
+                width: u32_at(p, 12)?,
+                height: u32_at(p, 16)?,
+                bpp: *p.get(20)?,
+                kind: match *p.get(21)? {
+                    0 => FramebufferType::Indexed,
+                    1 => FramebufferType::Rgb,
+                    2 => FramebufferType::EgaText,
+                    t => FramebufferType::Unknown(t),
+                },
+                // After a 16-bit reserved field; the indexed palette is not kept.
+                color_info: p
+                    .get(24..30)
+                    .map_or([0; 6], |c| c.try_into().unwrap_or([0; 6])),
+            }),
+            TAG_ELF_SECTIONS => Tag::ElfSections(ElfSections {
+                num: u32_at(p, 0)?,
+                entsize: u32_at(p, 4)?,
+                shndx: u32_at(p, 8)?,
+                headers: p.get(12..)?,
+            }),
+            TAG_ACPI_OLD => Tag::AcpiOldRsdp(p),
+            TAG_ACPI_NEW => Tag::AcpiNewRsdp(p),
+            _ => return None,
+        })
+    }
+}
+
+/// Iterator over the tags of a boot information structure.
+pub struct Tags<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for Tags<'a> {
+    type Item = Tag<'a>;
+
+    fn next(&mut self) -> Option<Tag<'a>> {
+        let kind = u32_at(self.rest, 0)?;
+        let size = u32_at(self.rest, 4)? as usize;
+        if kind == TAG_END || size < HEADER_SIZE || size > self.rest.len() {
+            self.rest = &[];
+            return None;
+        }
+        let payload = &self.rest[HEADER_SIZE..size];
+        let next = size.next_multiple_of(8).min(self.rest.len());
+        self.rest = &self.rest[next..];
+        Some(Tag::parse(kind, payload).unwrap_or(Tag::Other { kind, payload }))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Info<'a> {
+    bytes: &'a [u8],
+}
+
+/// Returns the boot information if `magic` says a Multiboot2 loader started us.
+///
+/// # Safety
+/// `mbi_addr` must be the EBX value passed in by the bootloader, still mapped.
+pub unsafe fn info(magic: u32, mbi_addr: u32) -> Option<Info<'static>> {
+    if magic != BOOTLOADER_MAGIC || mbi_addr == 0 || !mbi_addr.is_multiple_of(8) {
+        return None;
+    }
+    let total_size = (mbi_addr as *const u32).read();
+    let bytes = core::slice::from_raw_parts(mbi_addr as *const u8, total_size as usize);
+    Info::from_bytes(bytes)
+}
+
+impl<'a> Info<'a> {
+    /// Wraps a whole structure, `total_size` included; `None` if it is too short.
+    pub fn from_bytes(bytes: &'a [u8]) -> Option<Self> {
+        let total_size = u32_at(bytes, 0)? as usize;
+        if total_size < HEADER_SIZE {
+            return None;
+        }
+        Some(Self {
+            bytes: bytes.get(..total_size)?,
+        })
+    }
+
+    pub fn tags(&self) -> Tags<'a> {
+        Tags {
+            rest: &self.bytes[HEADER_SIZE..],
+        }
+    }
+
+    pub fn cmdline(&self) -> Option<&'a str> {
+        self.tags().find_map(|t| match t {
+            Tag::Cmdline(s) => Some(s),
+            _ => None,
+        })
+    }
+
+    pub fn boot_loader_name(&self) -> Option<&'a str> {
+        self.tags().find_map(|t| match t {
+            Tag::BootLoaderName(s) => Some(s),
+            _ => None,
+        })
+    }
+
+    pub fn modules(&self) -> impl Iterator<Item = Module<'a>> {
+        self.tags().filter_map(|t| match t {
+            Tag::Module(m) => Some(m),
+            _ => None,
+        })
+    }
+
+    /// (KiB below 1 MiB, KiB above 1 MiB).
+    pub fn basic_meminfo(&self) -> Option<(u32, u32)> {
+        self.tags().find_map(|t| match t {
+            Tag::BasicMeminfo {
+                mem_lower,
+                mem_upper,
+            } => Some((mem_lower, mem_upper)),
+            _ => None,
+        })
+    }
+
+    pub fn memory_map(&self) -> Option<MemoryMap<'a>> {
+        self.tags().find_map(|t| match t {
+            Tag::MemoryMap(m) => Some(m),
+            _ => None,
+        })
+    }
+
+    pub fn framebuffer_info(&self) -> Option<FramebufferInfo> {
+        self.tags().find_map(|t| match t {
+            Tag::Framebuffer(fb) => Some(fb),
+            _ => None,
+        })
+    }
+
+    /// The linear framebuffer set up by the loader, as for Multiboot 1.
+    pub fn framebuffer(&self) -> Option<Mode> {
+        self.framebuffer_info()?.mode()
+    }
+
+    pub fn elf_sections(&self) -> Option<ElfSections<'a>> {
+        self.tags().find_map(|t| match t {
+            Tag::ElfSections(s) => Some(s),
+            _ => None,
+        })
+    }
+
+    /// The ACPI RSDP copied by the loader, preferring the 2.0+ one.
+    pub fn rsdp(&self) -> Option<&'a [u8]> {
+        let new = self.tags().find_map(|t| match t {
+            Tag::AcpiNewRsdp(r) => Some(r),
+            _ => None,
+        });
+        new.or_else(|| {
+            self.tags().find_map(|t| match t {
+                Tag::AcpiOldRsdp(r) => Some(r),
+                _ => None,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a boot information structure the way a loader would.
+    struct Builder {
+        bytes: [u8; 512],
+        len: usize,
+    }
+
+    impl Builder {
+        fn new() -> Self {
+            Self {
+                bytes: [0; 512],
+                len: HEADER_SIZE,
+            }
+        }
+
+        fn put(&mut self, data: &[u8]) {
+            self.bytes[self.len..self.len + data.len()].copy_from_slice(data);
+            self.len += data.len();
+        }
+
+        fn tag(mut self, kind: u32, payload: &[&[u8]]) -> Self {
+            let size = HEADER_SIZE + payload.iter().map(|p| p.len()).sum::<usize>();
+            self.put(&kind.to_le_bytes());
+            self.put(&(size as u32).to_le_bytes());
+            for p in payload {
+                self.put(p);
+            }
+            self.len = self.len.next_multiple_of(8);
+            self
+        }
+
+        fn finish(mut self) -> ([u8; 512], usize) {
+            self = self.tag(TAG_END, &[]);
+            let len = self.len;
+            self.bytes[..4].copy_from_slice(&(len as u32).to_le_bytes());
+            (self.bytes, len)
+        }
+    }
+
+    fn region(base: u64, len: u64, kind: u32) -> [u8; 24] {
+        let mut e = [0; 24];
+        e[..8].copy_from_slice(&base.to_le_bytes());
+        e[8..16].copy_from_slice(&len.to_le_bytes());
+        e[16..20].copy_from_slice(&kind.to_le_bytes());
+        e
+    }
+
+    #[test_case]
+    fn strings_and_memory_tags_are_decoded() {
+        let (bytes, _) = Builder::new()
+            .tag(TAG_CMDLINE, &[b"loglevel=debug\0"])
+            .tag(
+                TAG_BASIC_MEMINFO,
+                &[&640u32.to_le_bytes(), &130048u32.to_le_bytes()],
+            )
+            .tag(
+                TAG_MODULE,
+                &[
+                    &0x20_0000u32.to_le_bytes(),
+                    &0x20_1000u32.to_le_bytes(),
+                    b"initrd\0",
+                ],
+            )
+            .tag(
+                TAG_MMAP,
+                &[
+                    &24u32.to_le_bytes(),
+                    &0u32.to_le_bytes(),
+                    &region(0, 0x9FC00, 1),
+                    &region(0xF0000, 0x10000, 2),
+                ],
+            )
+            .finish();
+        let info = Info::from_bytes(&bytes).unwrap();
+        assert_eq!(info.cmdline(), Some("loglevel=debug"));
+        assert_eq!(info.basic_meminfo(), Some((640, 130048)));
+        let module = info.modules().next().unwrap();
+        assert_eq!(
+            (module.start, module.end, module.cmdline),
+            (0x20_0000, 0x20_1000, "initrd")
+        );
+        let mut regions = info.memory_map().unwrap().regions();
+        assert_eq!(
+            regions.next(),
+            Some(MemoryRegion {
+                base: 0,
+                len: 0x9FC00,
+                kind: RegionKind::Available
+            })
+        );
+        assert_eq!(regions.next().map(|r| r.kind), Some(RegionKind::Reserved));
+        assert_eq!(regions.next(), None);
+        assert_eq!(info.framebuffer(), None);
+    }
+
+    #[test_case]
+    fn rgb_framebuffer_becomes_a_mode() {
+        let (bytes, _) = Builder::new()
+            .tag(
+                TAG_FRAMEBUFFER,
+                &[
+                    &0xFD00_0000u64.to_le_bytes(),
+                    &4096u32.to_le_bytes(),
+                    &1024u32.to_le_bytes(),
+                    &768u32.to_le_bytes(),
+                    &[32, 1, 0, 0],
+                    &[16, 8, 8, 8, 0, 8],
+                ],
+            )
+            .finish();
+        let mode = Info::from_bytes(&bytes).unwrap().framebuffer().unwrap();
+        assert_eq!(
+            (mode.base, mode.width, mode.height),
+            (0xFD00_0000, 1024, 768)
+        );
+        assert_eq!((mode.pitch, mode.bpp), (4096, 32));
+        assert_eq!((mode.format.red_pos, mode.format.blue_size), (16, 8));
+    }
+
+    #[test_case]
+    fn rsdp_prefers_acpi_2_and_unknown_tags_are_skipped() {
+        let (bytes, _) = Builder::new()
+            .tag(TAG_ACPI_OLD, &[b"RSD PTR old"])
+            .tag(21, &[&0x20_0000u32.to_le_bytes()])
+            .tag(TAG_ACPI_NEW, &[b"RSD PTR new"])
+            .finish();
+        let info = Info::from_bytes(&bytes).unwrap();
+        assert_eq!(info.rsdp(), Some(&b"RSD PTR new"[..]));
+        assert!(matches!(
+            info.tags().nth(1),
+            Some(Tag::Other { kind: 21, .. })
+        ));
+        assert_eq!(info.tags().count(), 3);
+    }
+
+    #[test_case]
+    fn truncated_structures_stop_the_walk() {
+        let (mut bytes, len) = Builder::new().tag(TAG_CMDLINE, &[b"a\0"]).finish();
+        // A tag claiming more bytes than the structure holds.
+        bytes[12..16].copy_from_slice(&(len as u32 * 2).to_le_bytes());
+        assert_eq!(Info::from_bytes(&bytes).unwrap().tags().count(), 0);
+        assert!(Info::from_bytes(&bytes[..4]).is_none());
+    }
+}
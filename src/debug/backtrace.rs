@@ -0,0 +1,101 @@
+/*
+    Stack backtraces by walking the frame-pointer chain.
+
+    The target spec forces frame pointers, so every function starts with
+    `push ebp; mov ebp, esp` and a frame looks like:
+
+        [ebp + 4]   return address into the caller
+        [ebp]       caller's ebp
+
+    The chain ends with a zero EBP (set by boot.asm, the GDT switch and the
+    initial frame of each kernel thread). The walk also stops on anything that
+    does not look like a frame - misaligned, not moving up the stack, or a
+    return address outside the kernel's code - so a corrupted stack gives a
+    short trace rather than a fault.
+*/
+
+use core::arch::asm;
+use core::fmt;
+
+use super::ksyms;
+
+/// Frames printed at most; deeper stacks are cut.
+pub const MAX_FRAMES: usize = 32;
+
+/// Return addresses found by following a frame-pointer chain.
+pub struct Frames {
+    ebp: usize,
+    depth: usize,
+}
+
+impl Frames {
+    /// Walks the chain starting at the frame whose base is `ebp`.
+    ///
+    /// # Safety
+    /// `ebp` must be 0 or point into a readable stack.
+    pub unsafe fn from_frame_pointer(ebp: usize) -> Self {
+        Self { ebp, depth: 0 }
+    }
+
+    /// Walks the calling function's stack, starting with the return address into its caller.
+    #[inline(always)]
+    pub fn here() -> Self {
+        let ebp: usize;
+        unsafe {
+            asm!("mov {}, ebp", out(reg) ebp, options(nomem, nostack, preserves_flags));
+            Self::from_frame_pointer(ebp)
+        }
+    }
+}
+
+impl Iterator for Frames {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.ebp == 0 || !self.ebp.is_multiple_of(4) || self.depth == MAX_FRAMES {
+            return None;
+        }
+        let frame = self.ebp as *const usize;
+        let (next, ret) = unsafe { (frame.read(), frame.add(1).read()) };
+        if !ksyms::in_text(ret) {
+            return None;
+        }
+        // Stacks grow down: callers' frames are always higher.
+        self.ebp = if next > self.ebp { next } else { 0 };
+        self.depth += 1;
+        Some(ret)
+    }
+}
+
+/// One line of a backtrace: `#n 0xADDR function+offset`.
+pub struct FrameLine {
+    pub index: usize,
+    pub addr: usize,
+}
+
+impl fmt::Display for FrameLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{} {:#010x} ", self.index, self.addr)?;
+        /*
+            A return address points past the call, which may already be the
+            next function when the call was the last instruction (noreturn).
+            Resolve the call itself, then report the offset of the address.
+        */
+        match ksyms::addr2sym(self.addr - 1) {
+            Some((name, offset)) => write!(f, "{}+{:#x}", name, offset + 1),
+            None => f.write_str("?"),
+        }
+    }
+}
+
+/// Writes the caller's backtrace to `out`, one `FrameLine` per line.
+#[inline(never)]
+pub fn write_to(out: &mut dyn fmt::Write) -> fmt::Result {
+    if !ksyms::available() {
+        writeln!(out, "(no kernel symbols, addresses only)")?;
+    }
+    for (index, addr) in Frames::here().enumerate() {
+        writeln!(out, "{}", FrameLine { index, addr })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,581 @@
+/*
+    GDB remote serial protocol stub on COM2.
+
+    Once `init` has run, the breakpoint (#BP) and debug (#DB) exceptions stop
+    the kernel and hand it to GDB on the other end of COM2:
+
+        (gdb) set architecture i386
+        (gdb) target remote /dev/ttyS1      # or the host side of QEMU's -serial
+
+    The whole machine stops while GDB is in control: interrupts are masked by
+    the exception gate and the stub polls the UART. Supported requests:
+
+        ?                       last stop reason
+        g / G, p / P            read / write registers
+        m / M                   read / write memory
+        Z0 / z0                 insert / remove a software breakpoint (int3)
+        c, s                    continue, single-step (EFLAGS.TF)
+        D, k                    detach (breakpoints removed) and resume
+
+    Memory accesses are not checked: without paging every physical address
+    reads something, and the kernel code is writable. Breakpoints in the
+    stub's own path (UART, spinlock) would deadlock it and are not guarded.
+*/
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::arch::x86::idt::{self, TrapFrame};
+use crate::drivers::serial::uart16550::Uart;
+use crate::drivers::serial::COM2_BASE;
+use crate::sync::irq_spinlock::IrqSpinLock;
+
+const BAUD: u32 = 115_200;
+
+/// Largest packet accepted, and advertised to GDB.
+const PACKET_SIZE: usize = 1024;
+
+/// Software breakpoints that can be set at the same time.
+const MAX_BREAKPOINTS: usize = 32;
+
+const INT3: u8 = 0xCC;
+
+/// EFLAGS.TF: trap after the next instruction.
+const EFLAGS_TF: u32 = 1 << 8;
+
+/// Registers in GDB's i386 order: eax ecx edx ebx esp ebp esi edi eip eflags cs ss ds es fs gs.
+const NUM_REGS: usize = 16;
+
+/// SIGTRAP, the stop reason reported for every entry into the stub.
+const SIGTRAP: u8 = 5;
+
+#[derive(Copy, Clone)]
+struct Breakpoint {
+    addr: usize,
+    saved: u8,
+}
+
+struct Stub {
+    uart: Uart,
+    breakpoints: [Option<Breakpoint>; MAX_BREAKPOINTS],
+    packet: [u8; PACKET_SIZE],
+}
+
+static STUB: IrqSpinLock<Stub> = IrqSpinLock::new(Stub {
+    uart: Uart::new(COM2_BASE),
+    breakpoints: [None; MAX_BREAKPOINTS],
+    packet: [0; PACKET_SIZE],
+});
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Sets up COM2 and routes breakpoints and single-steps to GDB.
+pub fn init() {
+    STUB.lock().uart.init(BAUD);
+    idt::set_handler(idt::BREAKPOINT, on_trap);
+    idt::set_handler(idt::DEBUG, on_trap);
+    ACTIVE.store(true, Ordering::Release);
+}
+
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Acquire)
+}
+
+/// Stops here and waits for GDB, if the stub is running.
+#[cfg(kernel)]
+#[inline(always)]
+pub fn breakpoint() {
+    if is_active() {
+        unsafe { core::arch::asm!("int3", options(nomem, nostack)) };
+    }
+}
+
+/// What the stub does once GDB lets the kernel go.
+enum Resume {
+    Continue,
+    Step,
+}
+
+fn on_trap(frame: &mut TrapFrame) {
+    let mut stub = STUB.lock();
+    // #BP leaves EIP after the int3: report (and resume at) the breakpoint itself.
+    if frame.vector == idt::BREAKPOINT as u32
+        && stub.find((frame.eip as usize).wrapping_sub(1)).is_some()
+    {
+        frame.eip -= 1;
+    }
+    frame.eflags &= !EFLAGS_TF;
+    stub.send_stop();
+    match stub.serve(frame) {
+        Resume::Continue => {}
+        Resume::Step => frame.eflags |= EFLAGS_TF,
+    }
+}
+
+impl Stub {
+    fn find(&self, addr: usize) -> Option<usize> {
+        self.breakpoints
+            .iter()
+            .position(|bp| bp.is_some_and(|bp| bp.addr == addr))
+    }
+
+    /// Handles requests until GDB resumes the kernel.
+    fn serve(&mut self, frame: &mut TrapFrame) -> Resume {
+        loop {
+            let len = self.receive();
+            // The packet buffer is reused for the reply: work on a copy of the request.
+            let mut request = [0u8; PACKET_SIZE];
+            request[..len].copy_from_slice(&self.packet[..len]);
+            let request = &request[..len];
+            let Some((&cmd, args)) = request.split_first() else {
+                self.send(b"");
+                continue;
+            };
+            match cmd {
+                b'?' => self.send_stop(),
+                b'g' => self.read_registers(frame),
+                b'G' => {
+                    write_registers(frame, args);
+                    self.send(b"OK");
+                }
+                b'p' => match parse_hex(args).and_then(|n| register(frame, n)) {
+                    Some(value) => {
+                        let mut out = [0u8; 8];
+                        encode_hex(&value.to_le_bytes(), &mut out);
+                        self.send(&out);
+                    }
+                    None => self.send(b"E01"),
+                },
+                b'P' => {
+                    let ok = split_once(args, b'=')
+                        .and_then(|(n, v)| Some((parse_hex(n)?, decode_u32_le(v)?)))
+                        .is_some_and(|(n, v)| set_register(frame, n, v));
+                    self.send(if ok { b"OK" } else { b"E01" });
+                }
+                b'm' => self.read_memory(args),
+                b'M' => {
+                    let ok = write_memory(args);
+                    self.send(if ok { b"OK" } else { b"E01" });
+                }
+                b'Z' | b'z' => self.breakpoint_request(cmd == b'Z', args),
+                b'c' => {
+                    resume_at(frame, args);
+                    return Resume::Continue;
+                }
+                b's' => {
+                    resume_at(frame, args);
+                    return Resume::Step;
+                }
+                b'D' | b'k' => {
+                    self.remove_all_breakpoints();
+                    if cmd == b'D' {
+                        self.send(b"OK");
+                    }
+                    return Resume::Continue;
+                }
+                b'q' if request.starts_with(b"qSupported") => self.send(b"PacketSize=400"),
+                b'q' if request == b"qAttached" => self.send(b"1"),
+                b'H' => self.send(b"OK"),
+                // Unsupported: an empty reply tells GDB to do without.
+                _ => self.send(b""),
+            }
+        }
+    }
+
+    fn read_registers(&mut self, frame: &TrapFrame) {
+        let mut out = [0u8; NUM_REGS * 8];
+        for n in 0..NUM_REGS {
+            let value = register(frame, n).unwrap_or(0);
+            encode_hex(&value.to_le_bytes(), &mut out[n * 8..n * 8 + 8]);
+        }
+        self.send(&out);
+    }
+
+    /// `m addr,length`
+    fn read_memory(&mut self, args: &[u8]) {
+        let Some((addr, len)) = split_once(args, b',')
+            .and_then(|(a, l)| Some((parse_hex(a)?, parse_hex(l)?)))
+            .filter(|&(_, len)| len.checked_mul(2).is_some_and(|n| n <= PACKET_SIZE))
+        else {
+            return self.send(b"E01");
+        };
+        let mut out = [0u8; PACKET_SIZE];
+        for i in 0..len {
+            let Some(at) = addr.checked_add(i) else {
+                return self.send(b"E01");
+            };
+            let byte = unsafe { (at as *const u8).read_volatile() };
+            encode_hex(&[byte], &mut out[i * 2..i * 2 + 2]);
+        }
+        self.send(&out[..len * 2]);
+    }
+
+    /// `Z0,addr,kind` / `z0,addr,kind`; only software breakpoints are supported.
+    fn breakpoint_request(&mut self, insert: bool, args: &[u8]) {
+        let mut fields = args.split(|&b| b == b',');
+        let (Some(b"0"), Some(addr)) = (fields.next(), fields.next().and_then(parse_hex)) else {
+            return self.send(b"");
+        };
+        let ok = if insert {
+            self.insert_breakpoint(addr)
+        } else {
+            self.remove_breakpoint(addr)
+        };
+        self.send(if ok { b"OK" } else { b"E01" });
+    }
+
+    fn insert_breakpoint(&mut self, addr: usize) -> bool {
+        if self.find(addr).is_some() {
+            return true;
+        }
+        let Some(slot) = self.breakpoints.iter_mut().find(|bp| bp.is_none()) else {
+            return false;
+        };
+        let ptr = addr as *mut u8;
+        unsafe {
+            *slot = Some(Breakpoint {
+                addr,
+                saved: ptr.read_volatile(),
+            });
+            ptr.write_volatile(INT3);
+        }
+        true
+    }
+
+    fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        let Some(i) = self.find(addr) else {
+            return false;
+        };
+        if let Some(bp) = self.breakpoints[i].take() {
+            unsafe { (bp.addr as *mut u8).write_volatile(bp.saved) };
+        }
+        true
+    }
+
+    fn remove_all_breakpoints(&mut self) {
+        for slot in self.breakpoints.iter_mut() {
+            if let Some(bp) = slot.take() {
+                unsafe { (bp.addr as *mut u8).write_volatile(bp.saved) };
+            }
+        }
+    }
+
+    fn send_stop(&mut self) {
+        let mut reply = *b"S00";
+        encode_hex(&[SIGTRAP], &mut reply[1..]);
+        self.send(&reply);
+    }
+
+    /// Waits for a well-formed `$data#cs` packet, acknowledges it and
+    /// leaves the data in `self.packet`. Returns its length.
+    fn receive(&mut self) -> usize {
+        // Set when the previous packet was cut short by the `$` of this one.
+        let mut started = false;
+        loop {
+            if !started {
+                while self.uart.read_byte() != b'$' {}
+            }
+            started = false;
+            let mut len = 0;
+            let mut sum: u8 = 0;
+            let complete = loop {
+                match self.uart.read_byte() {
+                    b'#' => break true,
+                    b'$' => {
+                        started = true;
+                        break false;
+                    }
+                    b if len < PACKET_SIZE => {
+                        self.packet[len] = b;
+                        sum = sum.wrapping_add(b);
+                        len += 1;
+                    }
+                    _ => break false,
+                }
+            };
+            if started {
+                continue;
+            }
+            if !complete {
+                self.uart.write_byte(b'-');
+                continue;
+            }
+            let checksum = [self.uart.read_byte(), self.uart.read_byte()];
+            if parse_hex(&checksum) == Some(sum as usize) {
+                self.uart.write_byte(b'+');
+                return len;
+            }
+            self.uart.write_byte(b'-');
+        }
+    }
+
+    /// Sends `$data#cs`, again until GDB acknowledges it with `+`.
+    fn send(&mut self, data: &[u8]) {
+        let sum = data.iter().fold(0u8, |s, &b| s.wrapping_add(b));
+        let mut checksum = [0u8; 2];
+        encode_hex(&[sum], &mut checksum);
+        loop {
+            self.uart.write_byte(b'$');
+            for &b in data {
+                self.uart.write_byte(b);
+            }
+            self.uart.write_byte(b'#');
+            self.uart.write_byte(checksum[0]);
+            self.uart.write_byte(checksum[1]);
+            // Anything but an acknowledgement (or a request to resend) is noise.
+            let ack = loop {
+                let b = self.uart.read_byte();
+                if b == b'+' || b == b'-' {
+                    break b;
+                }
+            };
+            if ack == b'+' {
+                return;
+            }
+        }
+    }
+}
+
+fn register(frame: &TrapFrame, n: usize) -> Option<u32> {
+    Some(match n {
+        0 => frame.eax,
+        1 => frame.ecx,
+        2 => frame.edx,
+        3 => frame.ebx,
+        4 => frame.esp(),
+        5 => frame.ebp,
+        6 => frame.esi,
+        7 => frame.edi,
+        8 => frame.eip,
+        9 => frame.eflags,
+        10 => frame.cs,
+        11 => stack_segment(),
+        12 => frame.ds,
+        13 => frame.es,
+        14 => frame.fs,
+        15 => frame.gs,
+        _ => return None,
+    })
+}
+
+/// SS is not in the frame: without a privilege change it is the live one.
+#[cfg(kernel)]
+fn stack_segment() -> u32 {
+    let ss: u16;
+    unsafe { core::arch::asm!("mov {0:x}, ss", out(reg) ss, options(nomem, nostack)) };
+    ss as u32
+}
+
+#[cfg(not(kernel))]
+fn stack_segment() -> u32 {
+    0
+}
+
+/// Applies a register write. ESP and the segments are fixed by the
+/// ring-0 frame layout, so writes to them are accepted and ignored.
+fn set_register(frame: &mut TrapFrame, n: usize, value: u32) -> bool {
+    let slot = match n {
+        0 => &mut frame.eax,
+        1 => &mut frame.ecx,
+        2 => &mut frame.edx,
+        3 => &mut frame.ebx,
+        5 => &mut frame.ebp,
+        6 => &mut frame.esi,
+        7 => &mut frame.edi,
+        8 => &mut frame.eip,
+        9 => &mut frame.eflags,
+        4 | 10..NUM_REGS => return true,
+        _ => return false,
+    };
+    *slot = value;
+    true
+}
+
+fn write_registers(frame: &mut TrapFrame, hex: &[u8]) {
+    for (n, chunk) in hex.chunks_exact(8).take(NUM_REGS).enumerate() {
+        if let Some(value) = decode_u32_le(chunk) {
+            set_register(frame, n, value);
+        }
+    }
+}
+
+/// `M addr,length:XX...`
+fn write_memory(args: &[u8]) -> bool {
+    let Some((header, data)) = split_once(args, b':') else {
+        return false;
+    };
+    let Some((addr, len)) =
+        split_once(header, b',').and_then(|(a, l)| Some((parse_hex(a)?, parse_hex(l)?)))
+    else {
+        return false;
+    };
+    if len.checked_mul(2) != Some(data.len()) {
+        return false;
+    }
+    for (i, pair) in data.chunks_exact(2).enumerate() {
+        let (Some(byte), Some(at)) = (parse_hex(pair), addr.checked_add(i)) else {
+            return false;
+        };
+        unsafe { (at as *mut u8).write_volatile(byte as u8) };
+    }
+    true
+}
+
+/// `c [addr]` / `s [addr]`: resume at `addr` when given.
+fn resume_at(frame: &mut TrapFrame, args: &[u8]) {
+    if let Some(addr) = parse_hex(args) {
+        frame.eip = addr as u32;
+    }
+}
+
+fn split_once(s: &[u8], sep: u8) -> Option<(&[u8], &[u8])> {
+    let i = s.iter().position(|&b| b == sep)?;
+    Some((&s[..i], &s[i + 1..]))
+}
+
+fn hex_digit(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Big-endian hex number, as used for addresses and lengths.
+fn parse_hex(s: &[u8]) -> Option<usize> {
+    if s.is_empty() || s.len() > 2 * size_of::<usize>() {
+        return None;
+    }
+    s.iter()
+        .try_fold(0usize, |n, &b| Some((n << 4) | hex_digit(b)? as usize))
+}
+
+/// Register value as GDB sends it: 4 bytes in target (little-endian) order.
+fn decode_u32_le(s: &[u8]) -> Option<u32> {
+    if s.len() != 8 {
+        return None;
+    }
+    let mut bytes = [0u8; 4];
+    for (byte, pair) in bytes.iter_mut().zip(s.chunks_exact(2)) {
+        *byte = parse_hex(pair)? as u8;
+    }
+    Some(u32::from_le_bytes(bytes))
+}
+
+fn encode_hex(bytes: &[u8], out: &mut [u8]) {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    for (pair, &b) in out.chunks_exact_mut(2).zip(bytes) {
+        pair[0] = DIGITS[(b >> 4) as usize];
+        pair[1] = DIGITS[(b & 0xF) as usize];
+    }
+}
+
+#[cfg(all(test, not(kernel)))]
+mod tests {
+    use super::*;
+    use crate::arch::x86::mock;
+    use std::vec::Vec;
+
+    fn stub() -> Stub {
+        Stub {
+            uart: Uart::new(COM2_BASE),
+            breakpoints: [None; MAX_BREAKPOINTS],
+            packet: [0; PACKET_SIZE],
+        }
+    }
+
+    /// Bytes GDB sends. The line status reads all ones: always ready.
+    fn from_gdb(bytes: &[u8]) {
+        for &b in bytes {
+            mock::queue_read(COM2_BASE, b as u32);
+        }
+    }
+
+    /// Bytes the stub sent since the last call.
+    fn to_gdb() -> Vec<u8> {
+        mock::take_writes()
+            .into_iter()
+            .filter(|&(port, _)| port == COM2_BASE)
+            .map(|(_, b)| b as u8)
+            .collect()
+    }
+
+    fn frame() -> TrapFrame {
+        unsafe { core::mem::zeroed() }
+    }
+
+    #[test_case]
+    fn parse_hex_takes_one_address_worth_of_digits() {
+        assert_eq!(parse_hex(b"0"), Some(0));
+        assert_eq!(parse_hex(b"1f"), Some(0x1F));
+        assert_eq!(parse_hex(b"DeAd"), Some(0xDEAD));
+        assert_eq!(parse_hex(b""), None);
+        assert_eq!(parse_hex(b"12g4"), None);
+        assert_eq!(parse_hex(&[b'f'; 2 * size_of::<usize>()]), Some(usize::MAX));
+        assert_eq!(parse_hex(&[b'0'; 2 * size_of::<usize>() + 1]), None);
+    }
+
+    #[test_case]
+    fn register_values_are_little_endian_hex() {
+        assert_eq!(decode_u32_le(b"78563412"), Some(0x1234_5678));
+        assert_eq!(decode_u32_le(b"7856341"), None);
+        assert_eq!(decode_u32_le(b"785634123"), None);
+        assert_eq!(decode_u32_le(b"7856341x"), None);
+
+        let mut out = [0u8; 8];
+        encode_hex(&0x1234_ABCDu32.to_le_bytes(), &mut out);
+        assert_eq!(&out, b"cdab3412");
+    }
+
+    #[test_case]
+    fn split_once_cuts_at_the_first_separator() {
+        assert_eq!(split_once(b"10,4", b','), Some((&b"10"[..], &b"4"[..])));
+        assert_eq!(split_once(b"1,2,3", b','), Some((&b"1"[..], &b"2,3"[..])));
+        assert_eq!(split_once(b"10,", b','), Some((&b"10"[..], &b""[..])));
+        assert_eq!(split_once(b"10", b','), None);
+    }
+
+    #[test_case]
+    fn receive_acks_only_packets_with_the_right_checksum() {
+        // Noise, a bad checksum, a packet cut short by a new one, then "g" (0x67).
+        from_gdb(b"+$g#00$m1$g#67");
+        let mut stub = stub();
+        assert_eq!(stub.receive(), 1);
+        assert_eq!(&stub.packet[..1], b"g");
+        assert_eq!(to_gdb(), b"-+");
+    }
+
+    #[test_case]
+    fn send_repeats_the_packet_until_gdb_acks_it() {
+        // 'O' + 'K' = 0x9A. Anything but + or - is ignored.
+        from_gdb(b"x-+");
+        stub().send(b"OK");
+        assert_eq!(to_gdb(), b"$OK#9a$OK#9a");
+    }
+
+    #[test_case]
+    fn register_writes_skip_the_fixed_ones() {
+        let mut frame = frame();
+        assert!(set_register(&mut frame, 8, 0x0020_1000));
+        assert_eq!(frame.eip, 0x0020_1000);
+        // ESP and the segments come from the frame layout.
+        assert!(set_register(&mut frame, 4, 1));
+        assert!(set_register(&mut frame, 10, 1));
+        assert_eq!((frame.esp_dummy, frame.cs), (0, 0));
+        assert!(!set_register(&mut frame, NUM_REGS, 1));
+
+        // eax, ecx, edx, ebx, esp; the rest of the packet is missing.
+        write_registers(&mut frame, b"01000000020000000300000004000000ffffffff0500");
+        assert_eq!((frame.eax, frame.ecx, frame.edx, frame.ebx), (1, 2, 3, 4));
+        assert_eq!((frame.esp_dummy, frame.ebp), (0, 0));
+    }
+
+    #[test_case]
+    fn memory_requests_with_overflowing_lengths_are_refused() {
+        let huge = std::format!("0,{:x}", usize::MAX / 2 + 1);
+        from_gdb(b"+");
+        stub().read_memory(huge.as_bytes());
+        assert_eq!(to_gdb(), b"$E01#a6");
+        assert!(!write_memory(std::format!("{huge}:").as_bytes()));
+    }
+}
@@ -0,0 +1,206 @@
+/*
+    Kernel symbol table, for turning code addresses into function names.
+
+    The table is not produced by the compiler: the Makefile links the kernel
+    once, lists its function symbols with scripts/gen_ksyms.sh and links again
+    with the result in the .ksyms section:
+
+        u32 count
+        (u32 addr, u32 name_offset) * count     sorted by address
+        NUL-terminated names, offsets from the start of the section
+
+    A kernel linked without the second pass (or an empty table) just resolves
+    nothing from it.
+
+    When the loader passed the kernel's ELF .symtab, it is registered with
+    `set_elf_symbols` and consulted first: unlike .ksyms it knows function
+    sizes, so padding after a function is not blamed on it. Its names are
+    mangled, though, and the name printed is the .ksyms one (demangled by
+    gen_ksyms.sh) at the start of that function.
+*/
+
+use core::fmt;
+use core::ptr::addr_of;
+use core::slice;
+
+use crate::boot::elf::SymbolTable;
+use crate::sync::OnceCell;
+
+extern "C" {
+    static __text_start: u8;
+    static __text_end: u8;
+    static __ksyms_start: u8;
+    static __ksyms_end: u8;
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Entry {
+    addr: u32,
+    name: u32,
+}
+
+fn section() -> &'static [u8] {
+    unsafe {
+        let start = addr_of!(__ksyms_start) as usize;
+        let end = addr_of!(__ksyms_end) as usize;
+        slice::from_raw_parts(start as *const u8, end.saturating_sub(start))
+    }
+}
+
+fn entries(blob: &[u8]) -> &[Entry] {
+    let Some(count) = blob.get(..4) else {
+        return &[];
+    };
+    let count = u32::from_ne_bytes([count[0], count[1], count[2], count[3]]) as usize;
+    // Never trust the count past the end of the section.
+    let count = count.min((blob.len() - 4) / size_of::<Entry>());
+    // The section is 4-aligned, like every field in it.
+    debug_assert!(blob.as_ptr().cast::<Entry>().is_aligned());
+    unsafe { slice::from_raw_parts(blob.as_ptr().add(4) as *const Entry, count) }
+}
+
+/// Whether `addr` lies in the kernel's code.
+pub fn in_text(addr: usize) -> bool {
+    let (start, end) = (
+        addr_of!(__text_start) as usize,
+        addr_of!(__text_end) as usize,
+    );
+    (start..end).contains(&addr)
+}
+
+/// Number of symbols in the .ksyms table.
+pub fn len() -> usize {
+    entries(section()).len()
+}
+
+static ELF_SYMBOLS: OnceCell<SymbolTable<'static>> = OnceCell::new();
+
+/// Adds the kernel's ELF symbol table; `false` if one was already set.
+pub fn set_elf_symbols(table: SymbolTable<'static>) -> bool {
+    ELF_SYMBOLS.set(table).is_ok()
+}
+
+/// Whether `addr2sym` can name anything at all.
+pub fn available() -> bool {
+    len() != 0 || ELF_SYMBOLS.get().is_some()
+}
+
+/// The function containing `addr`, with the offset of `addr` into it.
+pub fn addr2sym(addr: usize) -> Option<(&'static str, usize)> {
+    if !in_text(addr) {
+        return None;
+    }
+    resolve(ELF_SYMBOLS.get(), section(), addr)
+}
+
+/// `addr2sym` over a given ELF table and .ksyms blob.
+fn resolve<'a>(
+    elf: Option<&SymbolTable<'a>>,
+    blob: &'a [u8],
+    addr: usize,
+) -> Option<(&'a str, usize)> {
+    let Some(table) = elf else {
+        return lookup_ksyms(blob, addr);
+    };
+    let (mangled, offset) = table.lookup(addr)?;
+    // Without a .ksyms entry at the same start, the raw name still beats none.
+    let name = match lookup_ksyms(blob, addr - offset) {
+        Some((name, 0)) => name,
+        _ => mangled,
+    };
+    Some((name, offset))
+}
+
+/// The last .ksyms symbol at or below `addr`: the table has no sizes.
+fn lookup_ksyms(blob: &[u8], addr: usize) -> Option<(&str, usize)> {
+    let entries = entries(blob);
+    let i = entries.partition_point(|e| e.addr as usize <= addr);
+    let entry = entries.get(i.checked_sub(1)?)?;
+    let name = blob.get(entry.name as usize..)?;
+    let name = &name[..name.iter().position(|&b| b == 0)?];
+    Some((core::str::from_utf8(name).ok()?, addr - entry.addr as usize))
+}
+
+/// Formats a code address as `function+0xoffset`, or `?` when unknown.
+pub struct Symbolized(pub usize);
+
+impl fmt::Display for Symbolized {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match addr2sym(self.0) {
+            Some((name, offset)) => write!(f, "{}+{:#x}", name, offset),
+            None => f.write_str("?"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A .ksyms section, with the alignment the linker script gives it.
+    #[repr(C, align(4))]
+    struct Blob([u8; 128]);
+
+    fn blob(symbols: &[(u32, &str)]) -> Blob {
+        let mut b = Blob([0; 128]);
+        let put =
+            |b: &mut Blob, at: usize, v: u32| b.0[at..at + 4].copy_from_slice(&v.to_ne_bytes());
+        put(&mut b, 0, symbols.len() as u32);
+        let mut name = 4 + symbols.len() * size_of::<Entry>();
+        for (i, &(addr, s)) in symbols.iter().enumerate() {
+            put(&mut b, 4 + i * 8, addr);
+            put(&mut b, 8 + i * 8, name as u32);
+            b.0[name..name + s.len()].copy_from_slice(s.as_bytes());
+            name += s.len() + 1;
+        }
+        b
+    }
+
+    #[test_case]
+    fn ksyms_lookup_takes_the_closest_symbol_below() {
+        let b = blob(&[(0x1000, "kmain"), (0x1100, "kfs::a"), (0x1200, "kfs::b")]);
+        assert_eq!(lookup_ksyms(&b.0, 0xFFF), None);
+        assert_eq!(lookup_ksyms(&b.0, 0x1000), Some(("kmain", 0)));
+        assert_eq!(lookup_ksyms(&b.0, 0x10FF), Some(("kmain", 0xFF)));
+        assert_eq!(lookup_ksyms(&b.0, 0x1100), Some(("kfs::a", 0)));
+        // No size: everything past the last symbol is blamed on it.
+        assert_eq!(lookup_ksyms(&b.0, 0x1234), Some(("kfs::b", 0x34)));
+        assert_eq!(lookup_ksyms(&blob(&[]).0, 0x1000), None);
+        assert_eq!(lookup_ksyms(&[], 0x1000), None);
+    }
+
+    #[test_case]
+    fn elf_table_bounds_functions_and_ksyms_names_them() {
+        let b = blob(&[(0x1000, "kfs::kmain"), (0x1100, "kfs::helper")]);
+        let strings = b"\0_RNvCs1_3kfs5kmain\0_RNvCs1_3kfs6helper\0_RNvCs1_3kfs4lone\0";
+        let mut symbols = [0u8; 4 * 16];
+        // The null symbol, then three STT_FUNCs.
+        let entries = [
+            (0, 0, 0, 0),
+            (1, 0x1000, 0x80, 2),
+            (20, 0x1100, 0x10, 2),
+            (40, 0x1300, 8, 2),
+        ];
+        for (sym, (name, value, size, info)) in symbols.chunks_exact_mut(16).zip(entries) {
+            sym[..4].copy_from_slice(&(name as u32).to_le_bytes());
+            sym[4..8].copy_from_slice(&(value as u32).to_le_bytes());
+            sym[8..12].copy_from_slice(&(size as u32).to_le_bytes());
+            sym[12] = info;
+        }
+        let elf = SymbolTable::new(&symbols, strings);
+
+        assert_eq!(
+            resolve(Some(&elf), &b.0, 0x1010),
+            Some(("kfs::kmain", 0x10))
+        );
+        // Padding after kmain: .ksyms alone would still blame it.
+        assert_eq!(resolve(Some(&elf), &b.0, 0x10A0), None);
+        assert_eq!(resolve(None, &b.0, 0x10A0), Some(("kfs::kmain", 0xA0)));
+        // Nothing in .ksyms at that start: the ELF name as it is.
+        assert_eq!(
+            resolve(Some(&elf), &b.0, 0x1304),
+            Some(("_RNvCs1_3kfs4lone", 4))
+        );
+    }
+}
@@ -0,0 +1,6 @@
+#[cfg(kernel)]
+pub mod backtrace;
+pub mod gdbstub;
+pub mod ksyms;
+#[cfg(kernel)]
+pub mod panic;
@@ -0,0 +1,93 @@
+/*
+    Panic path.
+
+    Whatever was running is not coming back, so nothing here waits on anyone:
+    interrupts go off, the console locks are broken, and the report - message,
+    location, registers, a raw stack dump and the backtrace - is painted white
+    on red on the kernel console and mirrored to COM1 (also written without
+    its lock). The CPU then halts for good.
+
+    A panic raised while reporting (say, inside a console driver) only gets a
+    line on COM1 before halting.
+*/
+
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::backtrace;
+use super::ksyms;
+use crate::arch::x86::interrupts;
+use crate::arch::x86::registers::Registers;
+use crate::drivers::serial::_print_unlocked;
+use crate::subsystems::console;
+use crate::subsystems::console::vga::vga_color;
+use crate::subsystems::{sched, vt};
+
+/// Rows of four words shown from the stack pointer up.
+const STACK_DUMP_ROWS: usize = 4;
+
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// Writes everything both to the kernel console and to COM1.
+struct Report;
+
+impl Write for Report {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        crate::print!("{}", s);
+        _print_unlocked(format_args!("{}", s));
+        Ok(())
+    }
+}
+
+pub fn panic(info: &PanicInfo) -> ! {
+    interrupts::disable();
+    let regs = Registers::capture();
+    if PANICKING.swap(true, Ordering::Relaxed) {
+        _print_unlocked(format_args!(
+            "\nPANIC while panicking: {}\n",
+            info.message()
+        ));
+        interrupts::halt_forever();
+    }
+
+    // Interrupts are off and this CPU never returns to the holders.
+    unsafe { console::force_unlock() };
+    if !console::framebuffer_active() {
+        // The locks were just broken, so this cannot be `Busy`.
+        let _ = vt::switch_to(vt::KLOG_VT);
+    }
+    console::clear(vga_color::WHITE, vga_color::RED);
+    _print_unlocked(format_args!("\n"));
+    let _ = report(&mut Report, info, &regs);
+    interrupts::halt_forever()
+}
+
+fn report(out: &mut dyn Write, info: &PanicInfo, regs: &Registers) -> fmt::Result {
+    writeln!(out, "KERNEL PANIC: {}", info.message())?;
+    match info.location() {
+        Some(loc) => writeln!(out, "  at {} (thread {})", loc, sched::current().as_usize())?,
+        None => writeln!(out, "  (thread {})", sched::current().as_usize())?,
+    }
+    writeln!(out, "{}", regs)?;
+    writeln!(out, "Stack:")?;
+    dump_stack(out, regs.esp as usize)?;
+    writeln!(out, "Backtrace:")?;
+    backtrace::write_to(out)
+}
+
+/// Hex dump of the words at `esp`, marking those that point into the kernel code.
+fn dump_stack(out: &mut dyn Write, esp: usize) -> fmt::Result {
+    for row in 0..STACK_DUMP_ROWS {
+        let addr = esp + row * 16;
+        write!(out, "{:08x}:", addr)?;
+        for i in 0..4 {
+            // No paging yet: any address reads something, at worst open bus.
+            let word = unsafe { ((addr + i * 4) as *const usize).read_volatile() };
+            let mark = if ksyms::in_text(word) { '*' } else { ' ' };
+            write!(out, " {:08x}{}", word, mark)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
@@ -1 +1,2 @@
+pub mod pci;
 pub mod ps2;
@@ -0,0 +1,84 @@
+use crate::arch::x86::port::{Port, PortWriteOnly};
+
+/*
+    PCI configuration space through configuration mechanism #1: write the
+    address of a dword to CONFIG_ADDRESS, then access it at CONFIG_DATA.
+
+        bit 31     enable
+        23..16     bus
+        15..11     device
+        10..8      function
+        7..2       register (dword aligned)
+*/
+const CONFIG_ADDRESS: PortWriteOnly<u32> = PortWriteOnly::new(0xCF8);
+const CONFIG_DATA: Port<u32> = Port::new(0xCFC);
+
+const VENDOR_NONE: u16 = 0xFFFF;
+
+/// Offset of the first Base Address Register in a type 0 header.
+const BAR0: u8 = 0x10;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Location {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+impl Location {
+    pub fn read32(&self, offset: u8) -> u32 {
+        let addr = (1 << 31)
+            | (self.bus as u32) << 16
+            | (self.device as u32) << 11
+            | (self.function as u32) << 8
+            | (offset & 0xFC) as u32;
+        unsafe {
+            CONFIG_ADDRESS.write(addr);
+            CONFIG_DATA.read()
+        }
+    }
+
+    pub fn vendor_id(&self) -> u16 {
+        self.read32(0) as u16
+    }
+
+    pub fn device_id(&self) -> u16 {
+        (self.read32(0) >> 16) as u16
+    }
+
+    /// Base address of memory BAR `n` (0..5), or `None` for an I/O or 64-bit-high BAR.
+    pub fn memory_bar(&self, n: u8) -> Option<usize> {
+        let bar = self.read32(BAR0 + 4 * n);
+        if bar & 1 != 0 {
+            return None;
+        }
+        Some((bar & !0xF) as usize)
+    }
+}
+
+/// Finds the first function with the given vendor and device IDs (brute-force scan).
+pub fn find_device(vendor: u16, device: u16) -> Option<Location> {
+    for bus in 0..=255u8 {
+        for dev in 0..32u8 {
+            for function in 0..8u8 {
+                let loc = Location {
+                    bus,
+                    device: dev,
+                    function,
+                };
+                let id = loc.read32(0);
+                if id as u16 == VENDOR_NONE {
+                    // No function 0 means no device at all.
+                    if function == 0 {
+                        break;
+                    }
+                    continue;
+                }
+                if id as u16 == vendor && (id >> 16) as u16 == device {
+                    return Some(loc);
+                }
+            }
+        }
+    }
+    None
+}
@@ -1,17 +1,18 @@
-use crate::arch::x86::port::{inb, outb};
+use crate::arch::x86::port::{Port, PortReadOnly, PortWriteOnly};
+use crate::subsystems::sched;
 
 /// PS/2 controller data port (read/write).
 /// - Writing: sends a byte to the selected device.
 /// - Reading: reads data from the keyboard or mouse.
-pub const KBD_DATA: u16 = 0x60;
+pub const KBD_DATA: Port<u8> = Port::new(0x60);
 
 /// PS/2 controller status register (read-only).
 /// Contains bits that indicate input/output buffer state and errors.
-pub const KBD_STAT: u16 = 0x64;
+pub const KBD_STAT: PortReadOnly<u8> = PortReadOnly::new(0x64);
 
 /// PS/2 controller command register (write-only).
 /// Used to send commands to the controller itself, not to devices.
-pub const KBD_CMD: u16 = 0x64;
+pub const KBD_CMD: PortWriteOnly<u8> = PortWriteOnly::new(0x64);
 
 /// Status flag: Output Buffer Full (data available to read).
 /// Set when the controller has data ready in the data port.
@@ -22,18 +23,47 @@ const STAT_OBF: u8 = 1 << 0;
 const STAT_IBF: u8 = 1 << 1;
 
 pub fn data_available() -> bool {
-    unsafe { inb(KBD_STAT) & STAT_OBF != 0 }
+    unsafe { KBD_STAT.read() & STAT_OBF != 0 }
 }
 
 pub fn read_data() -> u8 {
-    unsafe { inb(KBD_DATA) }
+    unsafe { KBD_DATA.read() }
 }
 
+/// Sends `cmd` to the controller once it took the previous byte, letting the
+/// other threads run meanwhile. Thread context only, with no spinlock held.
 pub fn write_cmd(cmd: u8) {
     unsafe {
-        while inb(KBD_STAT) & STAT_IBF != 0 {
-            core::hint::spin_loop()
+        while KBD_STAT.read() & STAT_IBF != 0 {
+            sched::yield_now();
         }
-        outb(KBD_CMD, cmd);
+        KBD_CMD.write(cmd);
+    }
+}
+
+#[cfg(all(test, not(kernel)))]
+mod tests {
+    use super::*;
+    use crate::arch::x86::mock;
+
+    #[test_case]
+    fn commands_wait_until_the_input_buffer_drains() {
+        mock::queue_read(0x64, STAT_IBF as u32);
+        mock::queue_read(0x64, STAT_IBF as u32);
+        mock::queue_read(0x64, 0);
+        write_cmd(0xAE);
+        assert_eq!(mock::take_writes(), [(0x64, 0xAE)]);
+        // The status port is drained: all three polls happened.
+        assert_eq!(unsafe { KBD_STAT.read() }, 0xFF);
+    }
+
+    #[test_case]
+    fn data_is_read_only_once_the_output_buffer_fills() {
+        mock::queue_read(0x64, 0);
+        assert!(!data_available());
+        mock::queue_read(0x64, STAT_OBF as u32);
+        mock::queue_read(0x60, 0x1E);
+        assert!(data_available());
+        assert_eq!(read_data(), 0x1E);
     }
 }
@@ -0,0 +1,86 @@
+/*
+    French AZERTY layout, ASCII only: keys whose legend is not ASCII (é, è,
+    ç, à, ù, ², °, ¨, £, µ, §) give nothing, and there is no AltGr level yet,
+    so `#`, `@`, `{`, `[`, `|`, `\` and `~` cannot be typed.
+*/
+
+use super::types::{KeyCode, Modifiers};
+
+static MAP: [u8; 0x3A] = *b"\0\0\
+&\0\"'(-\0_\0\0)=\x08\t\
+azertyuiop^$\n\0\
+qs\
+dfghjklm\0\0\
+\0*\
+wxcvbn,;:!\
+\0*\0 ";
+
+static MAP_SHIFT: [u8; 0x3A] = *b"\0\0\
+1234567890\0+\x08\t\
+AZERTYUIOP\0\0\n\0\
+QS\
+DFGHJKLM%\0\
+\0\0\
+WXCVBN?./\0\
+\0*\0 ";
+
+pub fn translate_printable(sc: u8, mods: Modifiers) -> Option<KeyCode> {
+    let map = if mods.contains(Modifiers::SHIFT) {
+        &MAP_SHIFT
+    } else {
+        &MAP
+    };
+    match map.get(sc as usize) {
+        Some(&b) if b >= 0x20 => Some(KeyCode::Char(b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shift() -> Modifiers {
+        let mut mods = Modifiers::empty();
+        mods.insert(Modifiers::SHIFT);
+        mods
+    }
+
+    #[test_case]
+    fn letters_are_azerty() {
+        // The US Q, W, semicolon and M positions.
+        assert_eq!(
+            translate_printable(0x10, Modifiers::empty()),
+            Some(KeyCode::Char(b'a'))
+        );
+        assert_eq!(
+            translate_printable(0x11, shift()),
+            Some(KeyCode::Char(b'Z'))
+        );
+        assert_eq!(
+            translate_printable(0x27, Modifiers::empty()),
+            Some(KeyCode::Char(b'm'))
+        );
+        assert_eq!(
+            translate_printable(0x32, Modifiers::empty()),
+            Some(KeyCode::Char(b','))
+        );
+    }
+
+    #[test_case]
+    fn digits_need_shift_and_accents_are_skipped() {
+        assert_eq!(
+            translate_printable(0x02, Modifiers::empty()),
+            Some(KeyCode::Char(b'&'))
+        );
+        assert_eq!(
+            translate_printable(0x02, shift()),
+            Some(KeyCode::Char(b'1'))
+        );
+        assert_eq!(translate_printable(0x03, Modifiers::empty()), None);
+        assert_eq!(
+            translate_printable(0x28, shift()),
+            Some(KeyCode::Char(b'%'))
+        );
+    }
+}
@@ -15,3 +15,61 @@ pub fn translate_printable(sc: u8, mods: Modifiers) -> Option<KeyCode> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shift() -> Modifiers {
+        let mut mods = Modifiers::empty();
+        mods.insert(Modifiers::SHIFT);
+        mods
+    }
+
+    #[test_case]
+    fn letters_follow_shift() {
+        assert_eq!(
+            translate_printable(0x1E, Modifiers::empty()),
+            Some(KeyCode::Char(b'a'))
+        );
+        assert_eq!(
+            translate_printable(0x1E, shift()),
+            Some(KeyCode::Char(b'A'))
+        );
+        assert_eq!(
+            translate_printable(0x2C, Modifiers::empty()),
+            Some(KeyCode::Char(b'z'))
+        );
+    }
+
+    #[test_case]
+    fn digits_and_symbols_follow_shift() {
+        assert_eq!(
+            translate_printable(0x02, Modifiers::empty()),
+            Some(KeyCode::Char(b'1'))
+        );
+        assert_eq!(
+            translate_printable(0x02, shift()),
+            Some(KeyCode::Char(b'!'))
+        );
+        assert_eq!(
+            translate_printable(0x2B, shift()),
+            Some(KeyCode::Char(b'|'))
+        );
+        assert_eq!(
+            translate_printable(0x39, Modifiers::empty()),
+            Some(KeyCode::Char(b' '))
+        );
+    }
+
+    #[test_case]
+    fn control_keys_are_not_printable() {
+        // Escape, Enter, Backspace, Tab, left Ctrl.
+        for sc in [0x01, 0x1C, 0x0E, 0x0F, 0x1D] {
+            assert_eq!(translate_printable(sc, Modifiers::empty()), None);
+        }
+        // Past the table, and break codes.
+        assert_eq!(translate_printable(0x3A, Modifiers::empty()), None);
+        assert_eq!(translate_printable(0x9E, Modifiers::empty()), None);
+    }
+}
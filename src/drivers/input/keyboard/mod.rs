@@ -1,12 +1,63 @@
+mod keymap_fr;
 mod keymap_us;
 pub mod ps2;
 mod scancode_set1;
 pub mod types;
 
-use types::KeyEvent;
+use core::sync::atomic::{AtomicU8, Ordering};
 
-static mut STATE: ps2::State = ps2::State::new();
+use types::{KeyCode, KeyEvent, Modifiers};
+
+use crate::boot::cmdline::{ParamError, ParamValue};
+use crate::kernel_param;
+use crate::sync::irq_spinlock::IrqSpinLock;
+
+// Shift/Alt state and the pending 0xE0 prefix carry over between polls.
+static STATE: IrqSpinLock<ps2::State> = IrqSpinLock::new(ps2::State::new());
 
 pub fn poll_event() -> Option<KeyEvent> {
-    unsafe { ps2::poll_once(&mut STATE) }
+    ps2::poll_once(&mut STATE.lock())
+}
+
+/// Layout used to turn scan codes into characters.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Keymap {
+    Us,
+    Fr,
+}
+
+static KEYMAP: AtomicU8 = AtomicU8::new(Keymap::Us as u8);
+
+pub fn keymap() -> Keymap {
+    match KEYMAP.load(Ordering::Relaxed) {
+        x if x == Keymap::Fr as u8 => Keymap::Fr,
+        _ => Keymap::Us,
+    }
 }
+
+pub fn set_keymap(map: Keymap) {
+    KEYMAP.store(map as u8, Ordering::Relaxed);
+}
+
+fn translate_printable(sc: u8, mods: Modifiers) -> Option<KeyCode> {
+    match keymap() {
+        Keymap::Us => keymap_us::translate_printable(sc, mods),
+        Keymap::Fr => keymap_fr::translate_printable(sc, mods),
+    }
+}
+
+impl ParamValue for Keymap {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError> {
+        match value.ok_or(ParamError::MissingValue)? {
+            "us" => Ok(Keymap::Us),
+            "fr" => Ok(Keymap::Fr),
+            _ => Err(ParamError::Invalid("us or fr")),
+        }
+    }
+}
+
+kernel_param!("keymap", |map: Keymap| {
+    set_keymap(map);
+    Ok(())
+});
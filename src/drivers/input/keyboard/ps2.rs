@@ -1,17 +1,31 @@
 use super::types::{KeyCode, KeyEvent, Modifiers};
 use crate::drivers::bus::ps2::controller as ctl;
 
+/// Prefix byte announcing an extended (0xE0 xx) scancode.
+const EXTENDED_PREFIX: u8 = 0xE0;
+
 fn is_break(sc: u8) -> bool {
     sc & 0x80 != 0
 }
 
+/// Maps a set 1 make code to its function key number (F1..F12).
+fn function_key(sc: u8) -> Option<u8> {
+    match sc {
+        0x3B..=0x44 => Some(sc - 0x3A),
+        0x57 | 0x58 => Some(sc - 0x4C),
+        _ => None,
+    }
+}
+
 pub struct State {
     pub mods: Modifiers,
+    extended: bool,
 }
 impl State {
     pub const fn new() -> Self {
         Self {
             mods: Modifiers::empty(),
+            extended: false,
         }
     }
 }
@@ -42,8 +56,22 @@ impl State {
                 mods: self.mods,
                 pressed: true,
             },
+            0x38 => {
+                self.mods.insert(Modifiers::ALT);
+                KeyEvent {
+                    code: KeyCode::Unknown(sc),
+                    mods: self.mods,
+                    pressed: true,
+                }
+            }
             _ => {
-                if let Some(code) = super::keymap_us::translate_printable(sc, self.mods) {
+                if let Some(n) = function_key(sc) {
+                    KeyEvent {
+                        code: KeyCode::F(n),
+                        mods: self.mods,
+                        pressed: true,
+                    }
+                } else if let Some(code) = super::translate_printable(sc, self.mods) {
                     KeyEvent {
                         code,
                         mods: self.mods,
@@ -84,13 +112,43 @@ impl State {
                 mods: self.mods,
                 pressed: false,
             },
-            _ => KeyEvent {
-                code: KeyCode::Unknown(sc),
+            0x38 => {
+                self.mods.remove(Modifiers::ALT);
+                KeyEvent {
+                    code: KeyCode::Unknown(sc),
+                    mods: self.mods,
+                    pressed: false,
+                }
+            }
+            make => KeyEvent {
+                code: function_key(make).map_or(KeyCode::Unknown(sc), KeyCode::F),
                 mods: self.mods,
                 pressed: false,
             },
         }
     }
+    fn on_extended(&mut self, sc: u8) -> KeyEvent {
+        // E0 2A / E0 AA are fake shifts and must not touch the modifier state.
+        let code = match sc & 0x7F {
+            0x38 => {
+                // Right Alt (AltGr) counts as Alt.
+                if is_break(sc) {
+                    self.mods.remove(Modifiers::ALT);
+                } else {
+                    self.mods.insert(Modifiers::ALT);
+                }
+                KeyCode::Unknown(sc)
+            }
+            0x49 => KeyCode::PageUp,
+            0x51 => KeyCode::PageDown,
+            _ => KeyCode::Unknown(sc),
+        };
+        KeyEvent {
+            code,
+            mods: self.mods,
+            pressed: !is_break(sc),
+        }
+    }
 }
 
 pub fn poll_once(st: &mut State) -> Option<KeyEvent> {
@@ -98,6 +156,13 @@ pub fn poll_once(st: &mut State) -> Option<KeyEvent> {
         return None;
     }
     let sc = ctl::read_data();
+    if sc == EXTENDED_PREFIX {
+        st.extended = true;
+        return None;
+    }
+    if core::mem::take(&mut st.extended) {
+        return Some(st.on_extended(sc));
+    }
     Some(if is_break(sc) {
         st.on_break(sc)
     } else {
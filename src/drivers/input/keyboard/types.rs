@@ -4,6 +4,9 @@ pub enum KeyCode {
     Enter,
     Backspace,
     Tab,
+    PageUp,
+    PageDown,
+    F(u8), // function keys F1..F12
     Unknown(u8),
 }
 
@@ -52,3 +55,32 @@ impl KeyEvent {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn modifiers_insert_and_remove_single_bits() {
+        let mut m = Modifiers::empty();
+        m.insert(Modifiers::SHIFT);
+        m.insert(Modifiers::CAPS);
+        assert!(m.contains(Modifiers::SHIFT) && m.contains(Modifiers::CAPS));
+        assert!(!m.contains(Modifiers::CTRL));
+        m.remove(Modifiers::SHIFT);
+        assert_eq!(m.bits(), Modifiers::CAPS);
+    }
+
+    #[test_case]
+    fn only_pressed_keys_are_echoed() {
+        let ev = |code, pressed| KeyEvent {
+            code,
+            mods: Modifiers::empty(),
+            pressed,
+        };
+        assert_eq!(ev(KeyCode::Char(b'a'), true).printable_byte(), Some(b'a'));
+        assert_eq!(ev(KeyCode::Char(b'a'), false).printable_byte(), None);
+        assert_eq!(ev(KeyCode::Backspace, true).printable_byte(), Some(0x08));
+        assert_eq!(ev(KeyCode::F(1), true).printable_byte(), None);
+    }
+}
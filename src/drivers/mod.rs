@@ -1,3 +1,5 @@
 pub mod bus;
 pub mod input;
+pub mod qemu;
+pub mod serial;
 pub mod video;
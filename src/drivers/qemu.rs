@@ -0,0 +1,28 @@
+/*
+    QEMU's isa-debug-exit device, used by the test kernel to report its result:
+
+        -device isa-debug-exit,iobase=0xf4,iosize=0x04
+
+    Writing `value` to the port makes QEMU exit with status (value << 1) | 1,
+    so a run can never be mistaken for a normal QEMU exit (status 0).
+*/
+
+use crate::arch::x86::interrupts;
+use crate::arch::x86::port::PortWriteOnly;
+
+const ISA_DEBUG_EXIT_PORT: PortWriteOnly<u32> = PortWriteOnly::new(0xF4);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum ExitCode {
+    /// QEMU exits with status 33.
+    Success = 0x10,
+    /// QEMU exits with status 35.
+    Failed = 0x11,
+}
+
+/// Exits QEMU with `code`. Without the device (real hardware), halts instead.
+pub fn exit(code: ExitCode) -> ! {
+    unsafe { ISA_DEBUG_EXIT_PORT.write(code as u32) };
+    interrupts::halt_forever()
+}
@@ -0,0 +1,42 @@
+pub mod uart16550;
+
+use core::fmt;
+
+use crate::sync::irq_spinlock::IrqSpinLock;
+use uart16550::Uart;
+
+pub const COM1_BASE: u16 = 0x3F8;
+pub const COM2_BASE: u16 = 0x2F8;
+
+/// Serial log port (`make run` connects it to the terminal with `-serial stdio`).
+static COM1: IrqSpinLock<Uart> = IrqSpinLock::new(Uart::new(COM1_BASE));
+
+const COM1_BAUD: u32 = 115_200;
+
+pub fn init() {
+    COM1.lock().init(COM1_BAUD);
+    crate::info!("COM1 at {} baud", COM1_BAUD);
+}
+
+pub fn _print(args: fmt::Arguments) {
+    use core::fmt::Write;
+    let _ = COM1.lock().write_fmt(args);
+}
+
+/// Writes to COM1 without taking its lock, for reports made from places where
+/// the lock may be held or lock tracking must not recurse (panic, lockdep).
+pub fn _print_unlocked(args: fmt::Arguments) {
+    use core::fmt::Write;
+    let _ = Uart::new(COM1_BASE).write_fmt(args);
+}
+
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => { $crate::drivers::serial::_print(format_args!($($arg)*)) }
+}
+
+#[macro_export]
+macro_rules! serial_println {
+    () => { $crate::serial_print!("\n") };
+    ($($arg:tt)*) => { $crate::serial_print!("{}\n", format_args!($($arg)*)) }
+}
@@ -0,0 +1,152 @@
+use core::fmt;
+
+use crate::arch::x86::port::Port;
+
+/*
+    16550 UART registers, as offsets from the port base.
+    With DLAB (LCR bit 7) set, offsets 0/1 hold the baud rate divisor instead.
+*/
+const DATA: u16 = 0; // RBR (read) / THR (write)
+const INT_ENABLE: u16 = 1;
+const FIFO_CTRL: u16 = 2;
+const LINE_CTRL: u16 = 3;
+const MODEM_CTRL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+
+const LCR_DLAB: u8 = 0x80;
+const LCR_8N1: u8 = 0x03;
+/// Enable and clear both FIFOs, interrupt at 14 bytes.
+const FCR_ENABLE_CLEAR_14: u8 = 0xC7;
+/// DTR + RTS + OUT2 (OUT2 gates the IRQ line on PCs).
+const MCR_DTR_RTS_OUT2: u8 = 0x0B;
+
+const LSR_DATA_READY: u8 = 1 << 0;
+const LSR_THR_EMPTY: u8 = 1 << 5;
+
+/// Base clock of the PC UART divided by 16: the divisor for 115200 baud is 1.
+const MAX_BAUD: u32 = 115_200;
+
+/// A 16550-compatible serial port, polled (no interrupts).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Uart {
+    base: u16,
+}
+
+impl Uart {
+    pub const fn new(base: u16) -> Self {
+        Self { base }
+    }
+
+    pub fn base(&self) -> u16 {
+        self.base
+    }
+
+    fn reg(&self, offset: u16) -> Port<u8> {
+        Port::new(self.base + offset)
+    }
+
+    /// Programs 8N1 at `baud` with FIFOs on and interrupts off.
+    pub fn init(&self, baud: u32) {
+        let divisor = (MAX_BAUD / baud.clamp(1, MAX_BAUD)) as u16;
+        unsafe {
+            self.reg(INT_ENABLE).write(0x00);
+            self.reg(LINE_CTRL).write(LCR_DLAB);
+            self.reg(DATA).write(divisor as u8);
+            self.reg(INT_ENABLE).write((divisor >> 8) as u8);
+            self.reg(LINE_CTRL).write(LCR_8N1);
+            self.reg(FIFO_CTRL).write(FCR_ENABLE_CLEAR_14);
+            self.reg(MODEM_CTRL).write(MCR_DTR_RTS_OUT2);
+        }
+    }
+
+    fn line_status(&self) -> u8 {
+        unsafe { self.reg(LINE_STATUS).read() }
+    }
+
+    pub fn write_byte(&self, b: u8) {
+        while self.line_status() & LSR_THR_EMPTY == 0 {
+            core::hint::spin_loop();
+        }
+        unsafe { self.reg(DATA).write(b) };
+    }
+
+    /// Writes `bytes`, turning "\n" into "\r\n" for terminals.
+    pub fn write_bytes(&self, bytes: &[u8]) {
+        for &b in bytes {
+            if b == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(b);
+        }
+    }
+
+    pub fn try_read_byte(&self) -> Option<u8> {
+        if self.line_status() & LSR_DATA_READY == 0 {
+            return None;
+        }
+        Some(unsafe { self.reg(DATA).read() })
+    }
+
+    /// Spins until a byte arrives.
+    pub fn read_byte(&self) -> u8 {
+        loop {
+            if let Some(b) = self.try_read_byte() {
+                return b;
+            }
+            core::hint::spin_loop();
+        }
+    }
+}
+
+impl fmt::Write for Uart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(kernel)))]
+mod tests {
+    use super::*;
+    use crate::arch::x86::mock;
+
+    const BASE: u16 = 0x3F8;
+
+    #[test_case]
+    fn init_programs_the_divisor_then_8n1() {
+        Uart::new(BASE).init(9600);
+        assert_eq!(
+            mock::take_writes(),
+            [
+                (BASE + INT_ENABLE, 0),
+                (BASE + LINE_CTRL, LCR_DLAB as u32),
+                (BASE + DATA, 12),
+                (BASE + INT_ENABLE, 0),
+                (BASE + LINE_CTRL, LCR_8N1 as u32),
+                (BASE + FIFO_CTRL, FCR_ENABLE_CLEAR_14 as u32),
+                (BASE + MODEM_CTRL, MCR_DTR_RTS_OUT2 as u32),
+            ]
+        );
+    }
+
+    #[test_case]
+    fn newlines_go_out_as_crlf_once_the_transmitter_is_empty() {
+        mock::queue_read(BASE + LINE_STATUS, 0);
+        mock::queue_read(BASE + LINE_STATUS, LSR_THR_EMPTY as u32);
+        Uart::new(BASE).write_bytes(b"\n");
+        assert_eq!(
+            mock::take_writes(),
+            [(BASE + DATA, b'\r' as u32), (BASE + DATA, b'\n' as u32)]
+        );
+    }
+
+    #[test_case]
+    fn reads_wait_for_data_ready() {
+        let uart = Uart::new(BASE);
+        mock::queue_read(BASE + LINE_STATUS, LSR_THR_EMPTY as u32);
+        assert_eq!(uart.try_read_byte(), None);
+        mock::queue_read(BASE + LINE_STATUS, LSR_DATA_READY as u32);
+        mock::queue_read(BASE + DATA, b'k' as u32);
+        assert_eq!(uart.try_read_byte(), Some(b'k'));
+    }
+}
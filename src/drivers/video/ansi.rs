@@ -0,0 +1,318 @@
+/*
+    Minimal ANSI/VT100 escape sequence decoder.
+
+    The parser is fed one byte at a time and only reports what the byte means;
+    applying it to a screen is left to the console driver.
+
+        ESC [ <p1> ; <p2> ... <final>     CSI sequence
+        ESC 7 / ESC 8                     save / restore cursor (DEC)
+*/
+
+use super::vga_text::vga_color;
+
+const ESC: u8 = 0x1B;
+
+/// Maximum number of numeric parameters kept for one CSI sequence; extras are ignored.
+pub const MAX_PARAMS: usize = 8;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Params {
+    values: [u16; MAX_PARAMS],
+    len: usize,
+}
+
+impl Params {
+    const fn empty() -> Self {
+        Self {
+            values: [0; MAX_PARAMS],
+            len: 0,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u16] {
+        &self.values[..self.len]
+    }
+
+    /// Parameter `i`, with 0/missing replaced by `default` (VT100 semantics).
+    pub fn get_or(&self, i: usize, default: u16) -> u16 {
+        match self.as_slice().get(i) {
+            Some(&v) if v != 0 => v,
+            _ => default,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Action {
+    /// Regular byte to be displayed (or handled as a C0 control by the caller).
+    Print(u8),
+    /// C0 control met inside a sequence: run it if it moves the cursor
+    /// (CR, LF), ignore it otherwise, but never draw it.
+    Execute(u8),
+    /// SGR - Select Graphic Rendition (`ESC [ ... m`).
+    Sgr(Params),
+    /// CUU - cursor up by n rows.
+    CursorUp(u16),
+    /// CUD - cursor down by n rows.
+    CursorDown(u16),
+    /// CUF - cursor forward by n columns.
+    CursorForward(u16),
+    /// CUB - cursor back by n columns.
+    CursorBack(u16),
+    /// CUP - absolute cursor position, 1-based (row, col).
+    CursorPosition(u16, u16),
+    /// ED - erase in display (0: to end, 1: to start, 2: whole screen).
+    EraseDisplay(u16),
+    /// EL - erase in line (0: to end, 1: to start, 2: whole line).
+    EraseLine(u16),
+    SaveCursor,
+    RestoreCursor,
+    /// DECTCEM (`ESC [ ? 25 h` / `ESC [ ? 25 l`) - show or hide the cursor.
+    CursorVisible(bool),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+}
+
+pub struct Parser {
+    state: State,
+    params: Params,
+    // Slot receiving digits for the parameter being parsed (`None` past MAX_PARAMS).
+    current: Option<usize>,
+    // Whether digits have been seen since the last ';'.
+    pending: bool,
+    private: bool,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Ground,
+            params: Params::empty(),
+            current: None,
+            pending: false,
+            private: false,
+        }
+    }
+
+    /// Feeds one byte, returning the decoded action once a sequence is complete.
+    pub fn advance(&mut self, b: u8) -> Option<Action> {
+        match self.state {
+            State::Ground => {
+                if b == ESC {
+                    self.state = State::Escape;
+                    None
+                } else {
+                    Some(Action::Print(b))
+                }
+            }
+            State::Escape => {
+                self.state = State::Ground;
+                match b {
+                    b'[' => {
+                        self.state = State::Csi;
+                        self.params = Params::empty();
+                        self.current = None;
+                        self.pending = false;
+                        self.private = false;
+                        None
+                    }
+                    b'7' => Some(Action::SaveCursor),
+                    b'8' => Some(Action::RestoreCursor),
+                    ESC => {
+                        self.state = State::Escape;
+                        None
+                    }
+                    // Unsupported escape: swallow it rather than printing garbage.
+                    _ => None,
+                }
+            }
+            State::Csi => self.csi(b),
+        }
+    }
+
+    fn csi(&mut self, b: u8) -> Option<Action> {
+        match b {
+            b'0'..=b'9' => {
+                if !self.pending {
+                    self.current = self.push_param();
+                    self.pending = true;
+                }
+                if let Some(i) = self.current {
+                    let slot = &mut self.params.values[i];
+                    *slot = slot.saturating_mul(10).saturating_add((b - b'0') as u16);
+                }
+                None
+            }
+            b';' => {
+                if !self.pending {
+                    // Empty parameter, e.g. `ESC [ ; 5 H`.
+                    self.push_param();
+                }
+                self.pending = false;
+                None
+            }
+            b'?' | b'>' | b'=' => {
+                self.private = true;
+                None
+            }
+            // Intermediate bytes: accepted but ignored.
+            0x20..=0x2F => None,
+            0x40..=0x7E => {
+                self.state = State::Ground;
+                if self.private {
+                    return self.dispatch_private(b);
+                }
+                self.dispatch(b)
+            }
+            ESC => {
+                self.state = State::Escape;
+                None
+            }
+            // C0 controls inside a sequence are executed immediately, as on a VT100.
+            0x00..=0x1F => Some(Action::Execute(b)),
+            _ => {
+                self.state = State::Ground;
+                None
+            }
+        }
+    }
+
+    fn push_param(&mut self) -> Option<usize> {
+        if self.params.len < MAX_PARAMS {
+            self.params.values[self.params.len] = 0;
+            self.params.len += 1;
+            Some(self.params.len - 1)
+        } else {
+            None
+        }
+    }
+
+    fn dispatch_private(&self, final_byte: u8) -> Option<Action> {
+        match (final_byte, self.params.as_slice()) {
+            (b'h', [25]) => Some(Action::CursorVisible(true)),
+            (b'l', [25]) => Some(Action::CursorVisible(false)),
+            _ => None,
+        }
+    }
+
+    fn dispatch(&self, final_byte: u8) -> Option<Action> {
+        let p = &self.params;
+        Some(match final_byte {
+            b'm' => Action::Sgr(self.params),
+            b'A' => Action::CursorUp(p.get_or(0, 1)),
+            b'B' => Action::CursorDown(p.get_or(0, 1)),
+            b'C' => Action::CursorForward(p.get_or(0, 1)),
+            b'D' => Action::CursorBack(p.get_or(0, 1)),
+            b'H' | b'f' => Action::CursorPosition(p.get_or(0, 1), p.get_or(1, 1)),
+            b'J' => Action::EraseDisplay(p.as_slice().first().copied().unwrap_or(0)),
+            b'K' => Action::EraseLine(p.as_slice().first().copied().unwrap_or(0)),
+            b's' => Action::SaveCursor,
+            b'u' => Action::RestoreCursor,
+            _ => return None,
+        })
+    }
+}
+
+/*
+    ANSI color index (0..7, as used by SGR 30-37/40-47) to VGA palette entry.
+    Adding 8 gives the bright variant on both sides.
+*/
+const ANSI_TO_VGA: [u8; 8] = [
+    vga_color::BLACK,
+    vga_color::RED,
+    vga_color::GREEN,
+    vga_color::BROWN,
+    vga_color::BLUE,
+    vga_color::MAGENTA,
+    vga_color::CYAN,
+    vga_color::LIGHT_GRAY,
+];
+
+/// Applies SGR `params` to a VGA attribute byte (bg<<4 | fg) and the bold flag.
+///
+/// `default` is the attribute restored by SGR 0/39/49. Both the text and the
+/// framebuffer console use the 16-color VGA palette, so they share this.
+pub fn apply_sgr(color: u8, bold: bool, params: &[u16], default: u8) -> (u8, bool) {
+    let mut state = (color, bold);
+    if params.is_empty() {
+        state = sgr_one(state, 0, default);
+    }
+    for &p in params {
+        state = sgr_one(state, p, default);
+    }
+    state
+}
+
+fn sgr_one((color, mut bold): (u8, bool), p: u16, default: u8) -> (u8, bool) {
+    let mut fg = color & 0x0F;
+    let mut bg = (color >> 4) & 0x0F;
+    match p {
+        0 => {
+            bold = false;
+            fg = default & 0x0F;
+            bg = default >> 4;
+        }
+        1 => {
+            bold = true;
+            fg |= 0x08;
+        }
+        22 => {
+            bold = false;
+            fg &= 0x07;
+        }
+        30..=37 => fg = ANSI_TO_VGA[(p - 30) as usize] | if bold { 0x08 } else { 0 },
+        39 => fg = (default & 0x0F) | if bold { 0x08 } else { 0 },
+        40..=47 => bg = ANSI_TO_VGA[(p - 40) as usize],
+        49 => bg = default >> 4,
+        90..=97 => fg = ANSI_TO_VGA[(p - 90) as usize] | 0x08,
+        // Bright backgrounds only show as such on VGA with blinking disabled.
+        100..=107 => bg = ANSI_TO_VGA[(p - 100) as usize] | 0x08,
+        // Unsupported attributes (underline, blink, ...) are ignored.
+        _ => {}
+    }
+    (((bg & 0x0F) << 4) | (fg & 0x0F), bold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(p: &mut Parser, bytes: &[u8]) -> [Option<Action>; 4] {
+        let mut out = [None; 4];
+        let mut n = 0;
+        for &b in bytes {
+            if let Some(action) = p.advance(b) {
+                out[n] = Some(action);
+                n += 1;
+            }
+        }
+        out
+    }
+
+    #[test_case]
+    fn controls_inside_a_sequence_are_executed_not_printed() {
+        let mut p = Parser::new();
+        assert_eq!(
+            feed(&mut p, b"\x1b[1\r;2\x07H"),
+            [
+                Some(Action::Execute(b'\r')),
+                Some(Action::Execute(0x07)),
+                Some(Action::CursorPosition(1, 2)),
+                None
+            ]
+        );
+        // Back to ground: plain bytes print again.
+        assert_eq!(p.advance(b'x'), Some(Action::Print(b'x')));
+    }
+}
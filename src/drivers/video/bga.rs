@@ -0,0 +1,284 @@
+/*
+    Bochs Graphics Adapter, the "std" VGA of QEMU and Bochs.
+
+    A set of 16-bit registers behind an index/data port pair sets resolution
+    and depth directly, without a trip through the VGA BIOS or the
+    bootloader. The linear framebuffer is PCI BAR0 of device 1234:1111.
+*/
+
+use crate::arch::x86::port::{Port, PortWriteOnly};
+use crate::drivers::bus::pci;
+
+use super::framebuffer::{Mode, PixelFormat};
+
+const DISPI_IOPORT_INDEX: PortWriteOnly<u16> = PortWriteOnly::new(0x01CE);
+const DISPI_IOPORT_DATA: Port<u16> = Port::new(0x01CF);
+
+const INDEX_ID: u16 = 0x0;
+const INDEX_XRES: u16 = 0x1;
+const INDEX_YRES: u16 = 0x2;
+const INDEX_BPP: u16 = 0x3;
+const INDEX_ENABLE: u16 = 0x4;
+const INDEX_VIRT_WIDTH: u16 = 0x6;
+const INDEX_VIDEO_MEMORY_64K: u16 = 0xA;
+
+/// Oldest and newest interface revisions; ID5 added the video memory register.
+const ID0: u16 = 0xB0C0;
+const ID5: u16 = 0xB0C5;
+
+const ENABLED: u16 = 0x01;
+/// While set, XRES/YRES/BPP read back the maximum values instead of the current ones.
+const GETCAPS: u16 = 0x02;
+const LFB_ENABLED: u16 = 0x40;
+
+const PCI_VENDOR: u16 = 0x1234;
+const PCI_DEVICE: u16 = 0x1111;
+/// Where Bochs puts the framebuffer when it is not found on PCI (ISA config).
+const LFB_FALLBACK: usize = 0xE000_0000;
+
+/// Resolutions offered by `supported_modes`, filtered against the adapter limits.
+const RESOLUTIONS: [(u16, u16); 9] = [
+    (640, 480),
+    (800, 600),
+    (1024, 768),
+    (1152, 864),
+    (1280, 720),
+    (1280, 1024),
+    (1440, 900),
+    (1600, 1200),
+    (1920, 1080),
+];
+
+/// Depths the framebuffer driver can draw in.
+const DEPTHS: [u8; 4] = [32, 24, 16, 15];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ModeError {
+    /// No BGA-compatible adapter answered on the DISPI ports.
+    NotPresent,
+    /// The adapter or the framebuffer driver cannot do this width/height/depth.
+    Unsupported,
+    /// The adapter did not take the mode; the previous one was put back, but
+    /// whatever was on screen has to be redrawn.
+    Rejected,
+}
+
+/// The registers `set_mode` programs, to go back to an earlier mode.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct State {
+    enable: u16,
+    width: u16,
+    height: u16,
+    bpp: u16,
+}
+
+/// Adapter limits, as reported with GETCAPS.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Caps {
+    pub max_width: u16,
+    pub max_height: u16,
+    pub max_bpp: u8,
+    /// Size of video memory in bytes, when the adapter reports it.
+    pub video_memory: Option<usize>,
+}
+
+fn read(index: u16) -> u16 {
+    unsafe {
+        DISPI_IOPORT_INDEX.write(index);
+        DISPI_IOPORT_DATA.read()
+    }
+}
+
+fn write(index: u16, value: u16) {
+    unsafe {
+        DISPI_IOPORT_INDEX.write(index);
+        DISPI_IOPORT_DATA.write(value);
+    }
+}
+
+/// Interface revision (0xB0C0..=0xB0C5), or `None` without a BGA.
+pub fn version() -> Option<u16> {
+    let id = read(INDEX_ID);
+    (ID0..=ID5).contains(&id).then_some(id)
+}
+
+pub fn is_present() -> bool {
+    version().is_some()
+}
+
+pub fn capabilities() -> Option<Caps> {
+    let id = version()?;
+    let enable = read(INDEX_ENABLE);
+    write(INDEX_ENABLE, enable | GETCAPS);
+    let caps = Caps {
+        max_width: read(INDEX_XRES),
+        max_height: read(INDEX_YRES),
+        max_bpp: read(INDEX_BPP) as u8,
+        video_memory: (id >= ID5).then(|| read(INDEX_VIDEO_MEMORY_64K) as usize * 64 * 1024),
+    };
+    write(INDEX_ENABLE, enable);
+    Some(caps)
+}
+
+fn bytes_per_pixel(bpp: u8) -> usize {
+    (bpp as usize).div_ceil(8)
+}
+
+impl Caps {
+    pub fn supports(&self, width: u16, height: u16, bpp: u8) -> bool {
+        let fits_memory = self
+            .video_memory
+            .is_none_or(|mem| width as usize * height as usize * bytes_per_pixel(bpp) <= mem);
+        // The adapter only takes widths that are a multiple of 8.
+        width != 0
+            && height != 0
+            && width.is_multiple_of(8)
+            && width <= self.max_width
+            && height <= self.max_height
+            && DEPTHS.contains(&bpp)
+            && bpp <= self.max_bpp
+            && fits_memory
+    }
+}
+
+/// Every (width, height, bpp) from the standard list the adapter can display.
+pub fn supported_modes() -> impl Iterator<Item = (u16, u16, u8)> {
+    let caps = capabilities();
+    RESOLUTIONS
+        .into_iter()
+        .flat_map(|(w, h)| DEPTHS.into_iter().map(move |bpp| (w, h, bpp)))
+        .filter(move |&(w, h, bpp)| caps.is_some_and(|c| c.supports(w, h, bpp)))
+}
+
+/// The current mode, or text mode when the adapter is disabled.
+pub fn state() -> State {
+    State {
+        enable: read(INDEX_ENABLE) & !GETCAPS,
+        width: read(INDEX_XRES),
+        height: read(INDEX_YRES),
+        bpp: read(INDEX_BPP),
+    }
+}
+
+/// Goes back to a mode saved by `state`. Video memory is cleared on the way.
+pub fn restore(state: State) {
+    write(INDEX_ENABLE, 0);
+    write(INDEX_XRES, state.width);
+    write(INDEX_YRES, state.height);
+    write(INDEX_BPP, state.bpp);
+    write(INDEX_ENABLE, state.enable);
+}
+
+/// Physical address of the linear framebuffer.
+fn lfb_base() -> usize {
+    pci::find_device(PCI_VENDOR, PCI_DEVICE)
+        .and_then(|dev| dev.memory_bar(0))
+        .filter(|&base| base != 0)
+        .unwrap_or(LFB_FALLBACK)
+}
+
+/// Programs a graphics mode and returns the framebuffer to draw into.
+///
+/// Whatever was on screen (VGA text or a previous mode) is gone afterwards,
+/// unless the mode is refused before the adapter is touched.
+pub fn set_mode(width: u16, height: u16, bpp: u8) -> Result<Mode, ModeError> {
+    let caps = capabilities().ok_or(ModeError::NotPresent)?;
+    if !caps.supports(width, height, bpp) {
+        return Err(ModeError::Unsupported);
+    }
+    let previous = state();
+    write(INDEX_ENABLE, 0);
+    write(INDEX_XRES, width);
+    write(INDEX_YRES, height);
+    write(INDEX_BPP, bpp as u16);
+    write(INDEX_ENABLE, ENABLED | LFB_ENABLED);
+
+    // The adapter silently clamps values it does not like.
+    if read(INDEX_XRES) != width || read(INDEX_YRES) != height || read(INDEX_BPP) != bpp as u16 {
+        restore(previous);
+        return Err(ModeError::Rejected);
+    }
+    let format = match bpp {
+        16 => PixelFormat {
+            red_pos: 11,
+            red_size: 5,
+            green_pos: 5,
+            green_size: 6,
+            blue_pos: 0,
+            blue_size: 5,
+        },
+        15 => PixelFormat {
+            red_pos: 10,
+            red_size: 5,
+            green_pos: 5,
+            green_size: 5,
+            blue_pos: 0,
+            blue_size: 5,
+        },
+        _ => PixelFormat::XRGB8888,
+    };
+    Ok(Mode {
+        base: lfb_base(),
+        width: width as usize,
+        height: height as usize,
+        pitch: read(INDEX_VIRT_WIDTH) as usize * bytes_per_pixel(bpp),
+        bpp,
+        format,
+    })
+}
+
+/// Turns the graphics mode off, handing the display back to the VGA core.
+pub fn disable() {
+    if is_present() {
+        write(INDEX_ENABLE, 0);
+    }
+}
+
+#[cfg(all(test, not(kernel)))]
+mod tests {
+    use super::*;
+    use crate::arch::x86::mock;
+
+    const INDEX: u16 = 0x01CE;
+    const DATA: u16 = 0x01CF;
+
+    #[test_case]
+    fn clamped_mode_puts_the_previous_one_back() {
+        // ID, ENABLE, then XRES/YRES/BPP/video memory under GETCAPS.
+        for v in [ID5, 0, 1600, 1200, 32, 256] {
+            mock::queue_read(DATA, v as u32);
+        }
+        // The mode in use: 640x480x32.
+        for v in [ENABLED | LFB_ENABLED, 640, 480, 32] {
+            mock::queue_read(DATA, v as u32);
+        }
+        // Read back after the switch: the width was clamped.
+        mock::queue_read(DATA, 1024);
+        assert_eq!(set_mode(1280, 1024, 32), Err(ModeError::Rejected));
+
+        let writes = mock::take_writes();
+        let restored = [
+            (INDEX_ENABLE, 0),
+            (INDEX_XRES, 640),
+            (INDEX_YRES, 480),
+            (INDEX_BPP, 32),
+            (INDEX_ENABLE, ENABLED | LFB_ENABLED),
+        ]
+        .map(|(i, v)| [(INDEX, i as u32), (DATA, v as u32)]);
+        assert!(writes.ends_with(restored.as_flattened()));
+    }
+
+    #[test_case]
+    fn unsupported_mode_leaves_the_adapter_alone() {
+        for v in [ID5, 0, 1024, 768, 32, 256] {
+            mock::queue_read(DATA, v as u32);
+        }
+        assert_eq!(set_mode(1280, 1024, 32), Err(ModeError::Unsupported));
+        // Only the GETCAPS round trip reached a register.
+        let data: std::vec::Vec<_> = mock::take_writes()
+            .into_iter()
+            .filter(|&(port, _)| port == DATA)
+            .collect();
+        assert_eq!(data, [(DATA, GETCAPS as u32), (DATA, 0)]);
+    }
+}
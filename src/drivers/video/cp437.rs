@@ -0,0 +1,73 @@
+/*
+    Unicode -> code page 437 translation for the VGA ROM font.
+
+    0x20..0x7E is plain ASCII. The glyphs behind the C0 control codes and the
+    upper half are looked up in the tables below, which list the Unicode
+    character shown for each CP437 byte.
+*/
+
+/// Glyph used for code points the font cannot show (■).
+pub const REPLACEMENT: u8 = 0xFE;
+
+/// CP437 0x00..0x1F: the glyphs drawn for the control code positions.
+const LOW: [char; 32] = [
+    '\0', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼', //
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+];
+
+/// CP437 0x80..0xFF.
+const HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}',
+];
+
+/// Characters without their own glyph that are close enough to an existing one.
+const ALIASES: [(char, u8); 6] = [
+    ('β', 0xE1), // drawn with ß
+    ('μ', 0xE6), // Greek mu vs. micro sign
+    ('∑', 0xE4),
+    ('\u{2126}', 0xEA), // ohm sign, drawn with Greek omega
+    ('∈', 0xEE),
+    ('⌂', 0x7F),
+];
+
+/// Returns the CP437 byte showing `c`, or `None` if the font has no such glyph.
+pub fn from_char(c: char) -> Option<u8> {
+    if (' '..='~').contains(&c) {
+        return Some(c as u8);
+    }
+    if let Some(i) = HIGH.iter().position(|&h| h == c) {
+        return Some(0x80 + i as u8);
+    }
+    if c != '\0' {
+        if let Some(i) = LOW.iter().position(|&l| l == c) {
+            return Some(i as u8);
+        }
+    }
+    ALIASES.iter().find(|&&(a, _)| a == c).map(|&(_, b)| b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn from_char_maps_ascii_tables_and_aliases() {
+        assert_eq!(from_char('A'), Some(b'A'));
+        assert_eq!(from_char(' '), Some(0x20));
+        assert_eq!(from_char('~'), Some(0x7E));
+        assert_eq!(from_char('─'), Some(0xC4));
+        assert_eq!(from_char('☺'), Some(0x01));
+        assert_eq!(from_char('β'), Some(0xE1));
+        assert_eq!(from_char('⌂'), Some(0x7F));
+        assert_eq!(from_char('€'), None);
+        // NUL is a control code, not the blank glyph at 0x00.
+        assert_eq!(from_char('\0'), None);
+    }
+}
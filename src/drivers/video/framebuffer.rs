@@ -0,0 +1,456 @@
+/*
+    Linear framebuffer driver.
+
+    The framebuffer is an array of `height` rows of `pitch` bytes in physical
+    memory, used in place (no paging yet). Colors are passed around as
+    0x00RRGGBB and packed into the mode's pixel layout when written.
+
+    On top of the pixel primitives, `FramebufferConsole` renders text with the
+    built-in 8x8 font so the `Console` trait (and thus `println!`) works on a
+    graphical display.
+*/
+
+use core::ptr::NonNull;
+
+use super::ansi::{self, Action};
+use super::font8x8;
+use crate::arch::x86::mmio;
+use crate::subsystems::console::Console;
+
+/// Bit position and width of each channel within a pixel.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PixelFormat {
+    pub red_pos: u8,
+    pub red_size: u8,
+    pub green_pos: u8,
+    pub green_size: u8,
+    pub blue_pos: u8,
+    pub blue_size: u8,
+}
+
+impl PixelFormat {
+    /// 0x00RRGGBB, what 24/32 bpp modes use on PC hardware.
+    pub const XRGB8888: Self = Self {
+        red_pos: 16,
+        red_size: 8,
+        green_pos: 8,
+        green_size: 8,
+        blue_pos: 0,
+        blue_size: 8,
+    };
+
+    fn pack(&self, rgb: u32) -> u32 {
+        let channel = |v: u32, pos: u8, size: u8| {
+            let size = size.min(8) as u32;
+            if size == 0 {
+                0
+            } else {
+                ((v & 0xFF) >> (8 - size)) << pos
+            }
+        };
+        channel(rgb >> 16, self.red_pos, self.red_size)
+            | channel(rgb >> 8, self.green_pos, self.green_size)
+            | channel(rgb, self.blue_pos, self.blue_size)
+    }
+}
+
+/// Geometry of a linear framebuffer, as reported by the bootloader or set by a driver.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Mode {
+    pub base: usize,
+    pub width: usize,
+    pub height: usize,
+    /// Bytes per row, at least `width * bpp / 8`.
+    pub pitch: usize,
+    pub bpp: u8,
+    pub format: PixelFormat,
+}
+
+pub struct Framebuffer {
+    base: NonNull<u8>,
+    mode: Mode,
+}
+
+impl Framebuffer {
+    /// Returns `None` for a null base or a depth other than 15, 16, 24 or 32 bpp.
+    ///
+    /// # Safety
+    /// `mode` must describe mapped framebuffer memory that nothing else writes to.
+    pub unsafe fn new(mode: Mode) -> Option<Self> {
+        if !matches!(mode.bpp, 15 | 16 | 24 | 32) {
+            return None;
+        }
+        let base = NonNull::new(mode.base as *mut u8)?;
+        Some(Self { base, mode })
+    }
+
+    pub fn mode(&self) -> &Mode {
+        &self.mode
+    }
+
+    pub fn width(&self) -> usize {
+        self.mode.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.mode.height
+    }
+
+    fn bytes_per_pixel(&self) -> usize {
+        (self.mode.bpp as usize).div_ceil(8)
+    }
+
+    fn offset(&self, x: usize, y: usize) -> usize {
+        y * self.mode.pitch + x * self.bytes_per_pixel()
+    }
+
+    unsafe fn write_raw(&self, offset: usize, v: u32) {
+        let p = self.base.as_ptr() as usize + offset;
+        match self.mode.bpp {
+            32 => mmio::write(p, v),
+            24 => {
+                mmio::write(p, v as u8);
+                mmio::write(p + 1, (v >> 8) as u8);
+                mmio::write(p + 2, (v >> 16) as u8);
+            }
+            _ => mmio::write(p, v as u16),
+        }
+    }
+
+    unsafe fn read_raw(&self, offset: usize) -> u32 {
+        let p = self.base.as_ptr() as usize + offset;
+        match self.mode.bpp {
+            32 => mmio::read::<u32>(p),
+            24 => {
+                mmio::read::<u8>(p) as u32
+                    | (mmio::read::<u8>(p + 1) as u32) << 8
+                    | (mmio::read::<u8>(p + 2) as u32) << 16
+            }
+            _ => mmio::read::<u16>(p) as u32,
+        }
+    }
+
+    pub fn put_pixel(&mut self, x: usize, y: usize, rgb: u32) {
+        if x >= self.mode.width || y >= self.mode.height {
+            return;
+        }
+        let v = self.mode.format.pack(rgb);
+        unsafe { self.write_raw(self.offset(x, y), v) };
+    }
+
+    /// Fills a rectangle, clipped to the screen.
+    pub fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, rgb: u32) {
+        let x_end = (x + w).min(self.mode.width);
+        let y_end = (y + h).min(self.mode.height);
+        let v = self.mode.format.pack(rgb);
+        for py in y..y_end {
+            for px in x..x_end {
+                unsafe { self.write_raw(self.offset(px, py), v) };
+            }
+        }
+    }
+
+    /// Copies a `w`x`h` block of 0x00RRGGBB pixels (row-major) to (x, y), clipped to the screen.
+    pub fn blit(&mut self, x: usize, y: usize, w: usize, h: usize, src: &[u32]) {
+        let h = h.min(src.len() / w.max(1));
+        for row in 0..h.min(self.mode.height.saturating_sub(y)) {
+            for col in 0..w.min(self.mode.width.saturating_sub(x)) {
+                let v = self.mode.format.pack(src[row * w + col]);
+                unsafe { self.write_raw(self.offset(x + col, y + row), v) };
+            }
+        }
+    }
+
+    /// Inverts every pixel of a rectangle; doing it twice restores the original.
+    pub fn invert_rect(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        let x_end = (x + w).min(self.mode.width);
+        let y_end = (y + h).min(self.mode.height);
+        let mask = if self.mode.bpp == 32 {
+            u32::MAX
+        } else {
+            (1u32 << self.mode.bpp) - 1
+        };
+        for py in y..y_end {
+            for px in x..x_end {
+                let off = self.offset(px, py);
+                unsafe { self.write_raw(off, !self.read_raw(off) & mask) };
+            }
+        }
+    }
+
+    /// Moves the whole picture up by `lines` pixel rows and fills the bottom with `rgb`.
+    pub fn scroll_up(&mut self, lines: usize, rgb: u32) {
+        let lines = lines.min(self.mode.height);
+        let keep = self.mode.height - lines;
+        unsafe {
+            let base = self.base.as_ptr() as usize;
+            mmio::copy(base + lines * self.mode.pitch, base, keep * self.mode.pitch);
+        }
+        self.fill_rect(0, keep, self.mode.width, lines, rgb);
+    }
+}
+
+/// RGB values of the 16 VGA palette entries, so text colors match the text console.
+const VGA_PALETTE: [u32; 16] = [
+    0x000000, 0x0000AA, 0x00AA00, 0x00AAAA, 0xAA0000, 0xAA00AA, 0xAA5500, 0xAAAAAA, //
+    0x555555, 0x5555FF, 0x55FF55, 0x55FFFF, 0xFF5555, 0xFF55FF, 0xFFFF55, 0xFFFFFF,
+];
+
+const DEFAULT_COLOR: u8 = 0x07; // LightGray on Black
+
+const CELL_WIDTH: usize = font8x8::WIDTH;
+const CELL_HEIGHT: usize = font8x8::HEIGHT;
+
+/// Text console drawn into a framebuffer, one 8x8 cell per character.
+pub struct FramebufferConsole {
+    fb: Framebuffer,
+    cols: usize,
+    rows: usize,
+    row: usize,
+    col: usize,
+    color: u8, // (bg<<4 | fg), VGA palette indices
+    bold: bool,
+    saved: (usize, usize, u8),
+    ansi: ansi::Parser,
+    cursor_visible: bool,
+    // Whether the cursor is currently inverted on screen at (row, col).
+    cursor_drawn: bool,
+}
+
+impl FramebufferConsole {
+    /// Returns `None` if the screen cannot hold a single character.
+    pub fn new(fb: Framebuffer) -> Option<Self> {
+        let cols = fb.width() / CELL_WIDTH;
+        let rows = fb.height() / CELL_HEIGHT;
+        if cols == 0 || rows == 0 {
+            return None;
+        }
+        Some(Self {
+            fb,
+            cols,
+            rows,
+            row: 0,
+            col: 0,
+            color: DEFAULT_COLOR,
+            bold: false,
+            saved: (0, 0, DEFAULT_COLOR),
+            ansi: ansi::Parser::new(),
+            cursor_visible: true,
+            cursor_drawn: false,
+        })
+    }
+
+    pub fn framebuffer(&mut self) -> &mut Framebuffer {
+        &mut self.fb
+    }
+
+    pub fn width(&self) -> usize {
+        self.cols
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows
+    }
+
+    fn fg(&self) -> u32 {
+        VGA_PALETTE[(self.color & 0x0F) as usize]
+    }
+
+    fn bg(&self) -> u32 {
+        VGA_PALETTE[(self.color >> 4) as usize]
+    }
+
+    /*
+        The cursor is an inverted underline on the cell's bottom two pixel rows.
+        It is taken off before anything is drawn and put back afterwards, so
+        no cell contents need to be kept around to restore what it covered.
+    */
+    fn toggle_cursor(&mut self) {
+        let col = self.col.min(self.cols - 1);
+        let y = self.row * CELL_HEIGHT + CELL_HEIGHT - 2;
+        self.fb.invert_rect(col * CELL_WIDTH, y, CELL_WIDTH, 2);
+        self.cursor_drawn = !self.cursor_drawn;
+    }
+
+    fn hide_cursor(&mut self) {
+        if self.cursor_drawn {
+            self.toggle_cursor();
+        }
+    }
+
+    fn show_cursor(&mut self) {
+        if self.cursor_visible && !self.cursor_drawn {
+            self.toggle_cursor();
+        }
+    }
+
+    fn draw_glyph(&mut self, row: usize, col: usize, g: u8) {
+        let (fg, bg) = (self.fg(), self.bg());
+        let (x, y) = (col * CELL_WIDTH, row * CELL_HEIGHT);
+        for (dy, bits) in font8x8::glyph(g).iter().enumerate() {
+            for dx in 0..CELL_WIDTH {
+                // Bit 0 is the leftmost pixel in font8x8.
+                let rgb = if bits & (1 << dx) != 0 { fg } else { bg };
+                self.fb.put_pixel(x + dx, y + dy, rgb);
+            }
+        }
+    }
+
+    /// Blanks columns `from..to` of `row` with the current background.
+    fn clear_span(&mut self, row: usize, from: usize, to: usize) {
+        let bg = self.bg();
+        self.fb.fill_rect(
+            from * CELL_WIDTH,
+            row * CELL_HEIGHT,
+            (to - from) * CELL_WIDTH,
+            CELL_HEIGHT,
+            bg,
+        );
+    }
+
+    fn clear_rows(&mut self, from: usize, to: usize) {
+        let bg = self.bg();
+        let w = self.cols * CELL_WIDTH;
+        self.fb
+            .fill_rect(0, from * CELL_HEIGHT, w, (to - from) * CELL_HEIGHT, bg);
+    }
+
+    fn newline(&mut self) {
+        self.col = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+            return;
+        }
+        let bg = self.bg();
+        self.fb.scroll_up(CELL_HEIGHT, bg);
+    }
+
+    fn put_byte(&mut self, b: u8) {
+        match b {
+            b'\n' | b'\r' => self.execute(b),
+            ch => self.put_glyph(ch),
+        }
+    }
+
+    /// Runs a C0 control; those without a meaning here are dropped.
+    fn execute(&mut self, c: u8) {
+        match c {
+            b'\n' => self.newline(),
+            b'\r' => self.col = 0,
+            _ => {}
+        }
+    }
+
+    fn put_glyph(&mut self, g: u8) {
+        if self.col >= self.cols {
+            self.newline();
+        }
+        self.draw_glyph(self.row, self.col, g);
+        self.col += 1;
+    }
+
+    fn apply(&mut self, action: Action) {
+        // Same clamping as the VGA console: an explicit move cancels a pending wrap.
+        let col = self.col.min(self.cols - 1);
+        match action {
+            Action::Print(b) => self.put_byte(b),
+            Action::Execute(c) => self.execute(c),
+            Action::Sgr(params) => {
+                (self.color, self.bold) =
+                    ansi::apply_sgr(self.color, self.bold, params.as_slice(), DEFAULT_COLOR);
+            }
+            Action::CursorUp(n) => self.row = self.row.saturating_sub(n as usize),
+            Action::CursorDown(n) => self.row = (self.row + n as usize).min(self.rows - 1),
+            Action::CursorForward(n) => self.col = (col + n as usize).min(self.cols - 1),
+            Action::CursorBack(n) => self.col = col.saturating_sub(n as usize),
+            Action::CursorPosition(row, col) => {
+                self.row = (row as usize - 1).min(self.rows - 1);
+                self.col = (col as usize - 1).min(self.cols - 1);
+            }
+            Action::EraseDisplay(mode) => match mode {
+                0 => {
+                    self.clear_span(self.row, col, self.cols);
+                    self.clear_rows(self.row + 1, self.rows);
+                }
+                1 => {
+                    self.clear_rows(0, self.row);
+                    self.clear_span(self.row, 0, col + 1);
+                }
+                _ => self.clear_rows(0, self.rows),
+            },
+            Action::EraseLine(mode) => match mode {
+                0 => self.clear_span(self.row, col, self.cols),
+                1 => self.clear_span(self.row, 0, col + 1),
+                _ => self.clear_span(self.row, 0, self.cols),
+            },
+            Action::SaveCursor => self.saved = (self.row, self.col, self.color),
+            Action::RestoreCursor => {
+                (self.row, self.col, self.color) = self.saved;
+            }
+            Action::CursorVisible(visible) => self.cursor_visible = visible,
+        }
+    }
+}
+
+/// Safe because the console lives behind a lock (see `subsystems::console`).
+unsafe impl Send for FramebufferConsole {}
+
+impl Console for FramebufferConsole {
+    fn get_color_code(&self) -> u8 {
+        self.color
+    }
+
+    fn clear_screen(&mut self) {
+        self.cursor_drawn = false;
+        let bg = self.bg();
+        let (w, h) = (self.fb.width(), self.fb.height());
+        self.fb.fill_rect(0, 0, w, h, bg);
+        self.row = 0;
+        self.col = 0;
+        self.show_cursor();
+    }
+
+    fn set_color(&mut self, fg: u8, bg: u8) {
+        self.color = ((bg & 0x0F) << 4) | (fg & 0x0F);
+    }
+
+    fn write_byte(&mut self, b: u8) {
+        self.hide_cursor();
+        if let Some(action) = self.ansi.advance(b) {
+            self.apply(action);
+        }
+        self.show_cursor();
+    }
+
+    // font8x8 only has ASCII and CP437 0xB0..0xDF; other glyphs show as a square.
+    fn write_glyph(&mut self, g: u8) {
+        self.hide_cursor();
+        self.put_glyph(g);
+        self.show_cursor();
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.hide_cursor();
+        for &b in bytes {
+            if let Some(action) = self.ansi.advance(b) {
+                self.apply(action);
+            }
+        }
+        self.show_cursor();
+    }
+
+    fn backspace(&mut self) {
+        self.hide_cursor();
+        if self.col > 0 {
+            self.col -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.col = self.cols - 1;
+        } else {
+            self.show_cursor();
+            return;
+        }
+        self.clear_span(self.row, self.col, self.col + 1);
+        self.show_cursor();
+    }
+}
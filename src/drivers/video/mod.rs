@@ -1 +1,7 @@
+pub mod ansi;
+pub mod bga;
+pub mod cp437;
+pub mod font8x8;
+pub mod framebuffer;
+pub mod vga_regs;
 pub mod vga_text;
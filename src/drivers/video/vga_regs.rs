@@ -0,0 +1,339 @@
+use crate::arch::x86::mmio::Volatile;
+use crate::arch::x86::port::{Port, PortReadOnly, PortWriteOnly};
+
+/*
+    VGA register file. Each controller but the attribute controller is an
+    index/data port pair; the attribute controller shares one port for both
+    and toggles between them on every write (reset by reading INPUT_STATUS_1).
+*/
+const MISC_WRITE: PortWriteOnly<u8> = PortWriteOnly::new(0x3C2);
+const MISC_READ: PortReadOnly<u8> = PortReadOnly::new(0x3CC);
+const SEQ_ADDR: PortWriteOnly<u8> = PortWriteOnly::new(0x3C4);
+const SEQ_DATA: Port<u8> = Port::new(0x3C5);
+const CRTC_ADDR: PortWriteOnly<u8> = PortWriteOnly::new(0x3D4);
+const CRTC_DATA: Port<u8> = Port::new(0x3D5);
+const GC_ADDR: PortWriteOnly<u8> = PortWriteOnly::new(0x3CE);
+const GC_DATA: Port<u8> = Port::new(0x3CF);
+const AC_ADDR_DATA: PortWriteOnly<u8> = PortWriteOnly::new(0x3C0);
+const AC_READ: PortReadOnly<u8> = PortReadOnly::new(0x3C1);
+const INPUT_STATUS_1: PortReadOnly<u8> = PortReadOnly::new(0x3DA);
+
+/// Attribute controller index bit that gives the palette back to the display.
+const AC_PALETTE_ENABLE: u8 = 0x20;
+
+/// CRTC "end horizontal blanking" register (bit 7 must be set to read back 0x10-0x11).
+const CRTC_END_HBLANK: u8 = 0x03;
+/// CRTC "vertical retrace end" register (bit 7 write-protects 0x00-0x07).
+const CRTC_VRETRACE_END: u8 = 0x11;
+
+/// CRTC cursor start register: bits 0-4 first scanline, bit 5 disables the cursor.
+const CRTC_CURSOR_START: u8 = 0x0A;
+const CURSOR_DISABLE: u8 = 1 << 5;
+/// CRTC cursor end register: bits 0-4 last scanline, bits 5-6 skew (preserved).
+const CRTC_CURSOR_END: u8 = 0x0B;
+
+/// Attribute mode control register; bit 3 selects blink vs. 16 background colors.
+const AC_MODE_CONTROL: u8 = 0x10;
+const AC_BLINK_ENABLE: u8 = 1 << 3;
+
+/// Font plane (plane 2) seen through the 64 KiB window while it is mapped.
+const FONT_WINDOW: Volatile<u8> = unsafe { Volatile::new(0xA0000) };
+/// Bytes reserved per glyph in plane 2, whatever the character height.
+pub const GLYPH_STRIDE: usize = 32;
+pub const GLYPHS: usize = 256;
+
+pub fn seq_read(index: u8) -> u8 {
+    unsafe {
+        SEQ_ADDR.write(index);
+        SEQ_DATA.read()
+    }
+}
+
+pub fn seq_write(index: u8, value: u8) {
+    unsafe {
+        SEQ_ADDR.write(index);
+        SEQ_DATA.write(value);
+    }
+}
+
+pub fn crtc_read(index: u8) -> u8 {
+    unsafe {
+        CRTC_ADDR.write(index);
+        CRTC_DATA.read()
+    }
+}
+
+pub fn crtc_write(index: u8, value: u8) {
+    unsafe {
+        CRTC_ADDR.write(index);
+        CRTC_DATA.write(value);
+    }
+}
+
+pub fn gc_read(index: u8) -> u8 {
+    unsafe {
+        GC_ADDR.write(index);
+        GC_DATA.read()
+    }
+}
+
+pub fn gc_write(index: u8, value: u8) {
+    unsafe {
+        GC_ADDR.write(index);
+        GC_DATA.write(value);
+    }
+}
+
+/*
+    Accessing the attribute controller blanks the display until
+    AC_PALETTE_ENABLE is written back, so every access re-enables it.
+*/
+pub fn ac_read(index: u8) -> u8 {
+    unsafe {
+        INPUT_STATUS_1.read();
+        AC_ADDR_DATA.write(index);
+        let v = AC_READ.read();
+        INPUT_STATUS_1.read();
+        AC_ADDR_DATA.write(AC_PALETTE_ENABLE);
+        v
+    }
+}
+
+pub fn ac_write(index: u8, value: u8) {
+    unsafe {
+        INPUT_STATUS_1.read();
+        AC_ADDR_DATA.write(index);
+        AC_ADDR_DATA.write(value);
+        INPUT_STATUS_1.read();
+        AC_ADDR_DATA.write(AC_PALETTE_ENABLE);
+    }
+}
+
+/// Complete register state for one video mode.
+pub struct RegisterSet {
+    pub misc: u8,
+    pub seq: [u8; 5],
+    pub crtc: [u8; 25],
+    pub gc: [u8; 9],
+    pub ac: [u8; 21],
+}
+
+/// Programs every register of `regs`. The display is blanked while this runs.
+pub fn write_registers(regs: &RegisterSet) {
+    unsafe {
+        MISC_WRITE.write(regs.misc);
+    }
+    for (i, &v) in regs.seq.iter().enumerate() {
+        seq_write(i as u8, v);
+    }
+
+    // CRTC 0x00-0x07 are write-protected until the lock bit is cleared.
+    crtc_write(CRTC_END_HBLANK, crtc_read(CRTC_END_HBLANK) | 0x80);
+    crtc_write(CRTC_VRETRACE_END, crtc_read(CRTC_VRETRACE_END) & !0x80);
+    let mut crtc = regs.crtc;
+    crtc[CRTC_END_HBLANK as usize] |= 0x80;
+    crtc[CRTC_VRETRACE_END as usize] &= !0x80;
+    for (i, &v) in crtc.iter().enumerate() {
+        crtc_write(i as u8, v);
+    }
+
+    for (i, &v) in regs.gc.iter().enumerate() {
+        gc_write(i as u8, v);
+    }
+    unsafe {
+        for (i, &v) in regs.ac.iter().enumerate() {
+            INPUT_STATUS_1.read();
+            AC_ADDR_DATA.write(i as u8);
+            AC_ADDR_DATA.write(v);
+        }
+        INPUT_STATUS_1.read();
+        AC_ADDR_DATA.write(AC_PALETTE_ENABLE);
+    }
+}
+
+pub fn misc_read() -> u8 {
+    unsafe { MISC_READ.read() }
+}
+
+/// Sets the text cursor to scanlines `start..=end` of the cell, or hides it.
+pub fn set_cursor(start: u8, end: u8, visible: bool) {
+    let disable = if visible { 0 } else { CURSOR_DISABLE };
+    let s = crtc_read(CRTC_CURSOR_START) & 0xC0;
+    crtc_write(CRTC_CURSOR_START, s | disable | (start & 0x1F));
+    let e = crtc_read(CRTC_CURSOR_END) & 0xE0;
+    crtc_write(CRTC_CURSOR_END, e | (end & 0x1F));
+}
+
+/// With blink enabled attribute bit 7 blinks the cell; otherwise it selects
+/// the bright half of the palette for the background.
+pub fn set_blink(enabled: bool) {
+    let v = ac_read(AC_MODE_CONTROL);
+    let v = if enabled {
+        v | AC_BLINK_ENABLE
+    } else {
+        v & !AC_BLINK_ENABLE
+    };
+    ac_write(AC_MODE_CONTROL, v);
+}
+
+pub fn blink_enabled() -> bool {
+    ac_read(AC_MODE_CONTROL) & AC_BLINK_ENABLE != 0
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TextMode {
+    Text80x25,
+    Text80x50,
+    Text90x60,
+}
+
+impl TextMode {
+    pub const ALL: [TextMode; 3] = [
+        TextMode::Text80x25,
+        TextMode::Text80x50,
+        TextMode::Text90x60,
+    ];
+
+    /// (columns, rows) of the mode.
+    pub const fn dims(self) -> (usize, usize) {
+        match self {
+            TextMode::Text80x25 => (80, 25),
+            TextMode::Text80x50 => (80, 50),
+            TextMode::Text90x60 => (90, 60),
+        }
+    }
+
+    /// Scanlines per character cell, i.e. the font height the mode expects.
+    pub const fn char_height(self) -> usize {
+        match self {
+            TextMode::Text80x25 => 16,
+            TextMode::Text80x50 | TextMode::Text90x60 => 8,
+        }
+    }
+
+    pub fn registers(self) -> &'static RegisterSet {
+        match self {
+            TextMode::Text80x25 => &MODE_80X25,
+            TextMode::Text80x50 => &MODE_80X50,
+            TextMode::Text90x60 => &MODE_90X60,
+        }
+    }
+}
+
+/*
+    Register dumps for the supported text modes. 80x25 is BIOS mode 3
+    (9x16 cells, 720x400); 80x50 keeps its timings with 8-line cells;
+    90x60 uses 8x8 cells on a 720x480 raster.
+*/
+const GC_TEXT: [u8; 9] = [0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0E, 0x00, 0xFF];
+const AC_TEXT: [u8; 21] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x14, 0x07, 0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+    0x0C, 0x00, 0x0F, 0x08, 0x00,
+];
+
+static MODE_80X25: RegisterSet = RegisterSet {
+    misc: 0x67,
+    seq: [0x03, 0x00, 0x03, 0x00, 0x02],
+    crtc: [
+        0x5F, 0x4F, 0x50, 0x82, 0x55, 0x81, 0xBF, 0x1F, 0x00, 0x4F, 0x0D, 0x0E, 0x00, 0x00, 0x00,
+        0x00, 0x9C, 0x8E, 0x8F, 0x28, 0x1F, 0x96, 0xB9, 0xA3, 0xFF,
+    ],
+    gc: GC_TEXT,
+    ac: AC_TEXT,
+};
+
+static MODE_80X50: RegisterSet = RegisterSet {
+    misc: 0x67,
+    seq: [0x03, 0x00, 0x03, 0x00, 0x02],
+    crtc: [
+        0x5F, 0x4F, 0x50, 0x82, 0x55, 0x81, 0xBF, 0x1F, 0x00, 0x47, 0x06, 0x07, 0x00, 0x00, 0x00,
+        0x00, 0x9C, 0x8E, 0x8F, 0x28, 0x1F, 0x96, 0xB9, 0xA3, 0xFF,
+    ],
+    gc: GC_TEXT,
+    ac: AC_TEXT,
+};
+
+static MODE_90X60: RegisterSet = RegisterSet {
+    misc: 0xE7,
+    seq: [0x03, 0x01, 0x03, 0x00, 0x02],
+    crtc: [
+        0x6B, 0x59, 0x5A, 0x82, 0x60, 0x8D, 0x0B, 0x3E, 0x00, 0x47, 0x06, 0x07, 0x00, 0x00, 0x00,
+        0x00, 0xEA, 0x0C, 0xDF, 0x2D, 0x08, 0xE8, 0x05, 0xA3, 0xFF,
+    ],
+    gc: GC_TEXT,
+    ac: AC_TEXT,
+};
+
+/*
+    Plane 2 holds the font: 256 glyphs, 32 bytes apart, one byte per scanline
+    (MSB = leftmost pixel). In text mode it is hidden behind odd/even
+    addressing, so it is mapped linearly at 0xA0000 for the duration of `f`.
+*/
+fn with_font_plane<R>(f: impl FnOnce(Volatile<u8>) -> R) -> R {
+    let seq2 = seq_read(2);
+    let seq4 = seq_read(4);
+    let gc4 = gc_read(4);
+    let gc5 = gc_read(5);
+    let gc6 = gc_read(6);
+
+    seq_write(2, 1 << 2); // write plane 2 only
+    seq_write(4, seq4 | 0x04); // sequential addressing
+    gc_write(4, 2); // read plane 2
+    gc_write(5, gc5 & !0x10); // odd/even off
+    gc_write(6, (gc6 & 0x01) | 0x04); // 0xA0000-0xAFFFF, chain off
+
+    let r = f(FONT_WINDOW);
+
+    seq_write(2, seq2);
+    seq_write(4, seq4);
+    gc_write(4, gc4);
+    gc_write(5, gc5);
+    gc_write(6, gc6);
+    r
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FontError {
+    /// Glyph data is not exactly 256 * height bytes.
+    BadLength,
+    /// Height is 0 or larger than the 32 scanlines a glyph slot can hold.
+    BadHeight,
+}
+
+/// Uploads a 256-glyph bitmap font of `height` scanlines per glyph into plane 2.
+pub fn write_font(glyphs: &[u8], height: usize) -> Result<(), FontError> {
+    if height == 0 || height > GLYPH_STRIDE {
+        return Err(FontError::BadHeight);
+    }
+    if glyphs.len() != GLYPHS * height {
+        return Err(FontError::BadLength);
+    }
+    write_font_with(|c, line| {
+        if line < height {
+            glyphs[c * height + line]
+        } else {
+            0
+        }
+    });
+    Ok(())
+}
+
+/// Fills every glyph slot of plane 2 with `line(glyph, scanline)`.
+pub fn write_font_with(line: impl Fn(usize, usize) -> u8) {
+    with_font_plane(|plane| {
+        for c in 0..GLYPHS {
+            for l in 0..GLYPH_STRIDE {
+                unsafe { plane.add(c * GLYPH_STRIDE + l).write(line(c, l)) };
+            }
+        }
+    });
+}
+
+/// Reads the 256 glyph slots of plane 2 (32 bytes each) into `out`.
+pub fn read_font(out: &mut [u8; GLYPHS * GLYPH_STRIDE]) {
+    with_font_plane(|plane| {
+        for (i, b) in out.iter_mut().enumerate() {
+            *b = unsafe { plane.add(i).read() };
+        }
+    });
+}
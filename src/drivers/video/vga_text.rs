@@ -1,15 +1,29 @@
-use core::ptr::{read_volatile, write_volatile, NonNull};
-
-use crate::arch::x86::port::outb;
+use super::ansi::{self, Action};
+use super::vga_regs::{self, FontError, TextMode, GLYPHS, GLYPH_STRIDE};
+use crate::arch::x86::mmio::Volatile;
 use crate::subsystems::console::Console;
 use crate::sync::spinlock::SpinLock;
 
-pub const HEIGHT: usize = 25;
-pub const WIDTH: usize = 80;
+/// Largest text mode supported (90x60); per-console buffers are sized for it.
+pub const MAX_WIDTH: usize = 90;
+pub const MAX_HEIGHT: usize = 60;
+
+/// Number of lines kept after they scroll off the top of the screen.
+pub const SCROLLBACK_LINES: usize = 400;
 
 const VGA_BASE: usize = 0xb8000;
-const VGA_CRTC_ADDR: u16 = 0x3D4;
-const VGA_CRTC_DATA: u16 = 0x3D5;
+
+/*
+    CRTC registers: the start address selects which cell of text memory is
+    displayed top-left; the cursor location is absolute in text memory.
+*/
+const CRTC_START_ADDR_HIGH: u8 = 0x0C;
+const CRTC_START_ADDR_LOW: u8 = 0x0D;
+const CRTC_CURSOR_LOC_HIGH: u8 = 0x0E;
+const CRTC_CURSOR_LOC_LOW: u8 = 0x0F;
+
+/// Cells in the 32 KiB text memory window at 0xB8000..0xBFFFF.
+const VGA_MEM_CELLS: usize = 0x8000 / 2;
 
 pub mod vga_color {
     pub const BLACK: u8 = 0x0;
@@ -34,11 +48,53 @@ const fn color_code(fg: u8, bg: u8) -> u8 {
     ((bg & 0x0F) << 4) | (fg & 0x0F)
 }
 
+const DEFAULT_COLOR: u8 = color_code(vga_color::LIGHT_GRAY, vga_color::BLACK);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CursorShape {
+    /// Bottom two scanlines of the cell (the BIOS default).
+    Underline,
+    /// The whole character cell, e.g. for overwrite mode.
+    Block,
+    /// Explicit first/last scanlines, 0 being the top of the cell.
+    Scanlines(u8, u8),
+}
+
+impl CursorShape {
+    fn scanlines(self, char_height: usize) -> (u8, u8) {
+        let last = (char_height - 1) as u8;
+        match self {
+            CursorShape::Underline => (last.saturating_sub(1), last),
+            CursorShape::Block => (0, last),
+            CursorShape::Scanlines(start, end) => (start.min(last), end.min(last)),
+        }
+    }
+}
+
 pub struct VgaTextConsole {
     row: usize,
     col: usize,
-    color: u8,         // (bg<<4 | fg)
-    buf: NonNull<u16>, // MMIO 0xb8000
+    color: u8,  // (bg<<4 | fg)
+    bold: bool, // SGR 1: render the foreground with the bright palette half
+    saved: (usize, usize, u8),
+    ansi: ansi::Parser,
+    buf: Volatile<u16>, // MMIO 0xb8000
+    visible: bool,      // whether this console currently owns the VGA memory
+    origin: usize,      // VGA memory cell shown top-left (CRTC start address)
+    width: usize,
+    height: usize,
+    // Screen contents (row stride = width), mirrored to `buf` while visible.
+    cells: [u16; MAX_WIDTH * MAX_HEIGHT],
+    /*
+        Scrollback ring: `history[hist_next]` is the slot for the next line pushed.
+        A zero cell has never been written and is displayed as a blank.
+    */
+    history: [[u16; MAX_WIDTH]; SCROLLBACK_LINES],
+    hist_next: usize,
+    hist_len: usize,
+    view_offset: usize, // lines scrolled back from the live screen (0 = live)
+    cursor_shape: CursorShape,
+    cursor_visible: bool,
 }
 
 impl VgaTextConsole {
@@ -46,97 +102,380 @@ impl VgaTextConsole {
         Self {
             row: 0,
             col: 0,
-            color: color_code(7, 0), // LightGray on Black
-            buf: NonNull::new(VGA_BASE as *mut u16).unwrap(),
+            color: DEFAULT_COLOR, // LightGray on Black
+            bold: false,
+            saved: (0, 0, DEFAULT_COLOR),
+            ansi: ansi::Parser::new(),
+            buf: unsafe { Volatile::new(VGA_BASE) },
+            visible: false,
+            origin: 0,
+            width: 80,
+            height: 25,
+            cells: [0; MAX_WIDTH * MAX_HEIGHT],
+            history: [[0; MAX_WIDTH]; SCROLLBACK_LINES],
+            hist_next: 0,
+            hist_len: 0,
+            view_offset: 0,
+            cursor_shape: CursorShape::Underline,
+            cursor_visible: true,
         }
     }
 
-    pub fn get_color_code(&self) -> u8 {
-        self.color
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn blank(&self) -> u16 {
+        ((self.color as u16) << 8) | (b' ' as u16)
+    }
+
+    /*
+        Adapt to a new screen size. The lines ending at the cursor are kept
+        (older ones go to the scrollback) and moved to the top with the old
+        row stride, then re-laid out with the new one. Each pass walks rows in
+        the direction where `copy_within` cannot clobber rows not yet moved.
+    */
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let (width, height) = (width.clamp(1, MAX_WIDTH), height.clamp(1, MAX_HEIGHT));
+        self.snap_to_bottom();
+        let old_width = self.width;
+        let keep = self.height.min(height);
+        let first = (self.row + 1).saturating_sub(keep);
+        for r in 0..first {
+            self.push_history(r);
+        }
+        self.cells
+            .copy_within(first * old_width..(first + keep) * old_width, 0);
+        let n = old_width.min(width);
+        if width < old_width {
+            for r in 0..keep {
+                self.cells
+                    .copy_within(r * old_width..r * old_width + n, r * width);
+            }
+        } else {
+            for r in (0..keep).rev() {
+                self.cells
+                    .copy_within(r * old_width..r * old_width + n, r * width);
+            }
+        }
+        let blank = self.blank();
+        for r in 0..keep {
+            self.cells[r * width + n..(r + 1) * width].fill(blank);
+        }
+        self.cells[keep * width..width * height].fill(blank);
+
+        self.width = width;
+        self.height = height;
+        self.row -= first;
+        self.col = self.col.min(width);
+        self.saved.0 = self.saved.0.saturating_sub(first).min(height - 1);
+        self.saved.1 = self.saved.1.min(width);
+        if self.visible {
+            self.show();
+        }
     }
 
     fn pack(&self, ch: u8) -> u16 {
         ((self.color as u16) << 8) | ch as u16
     }
 
-    unsafe fn cell_ptr(&self, row: usize, col: usize) -> *mut u16 {
-        self.buf.as_ptr().add(row * WIDTH + col)
+    /// Writes one cell of the physical screen, bypassing the shadow buffer.
+    unsafe fn write_vga(&self, index: usize, v: u16) {
+        self.buf.add(self.origin + index).write(v);
     }
 
-    unsafe fn write_cell(&self, row: usize, col: usize, v: u16) {
-        write_volatile(self.cell_ptr(row, col), v);
+    fn write_cell(&mut self, row: usize, col: usize, v: u16) {
+        let index = row * self.width + col;
+        self.cells[index] = v;
+        if self.visible && self.view_offset == 0 {
+            unsafe { self.write_vga(index, v) };
+        }
     }
 
-    unsafe fn read_cell(&self, row: usize, col: usize) -> u16 {
-        read_volatile(self.cell_ptr(row, col))
+    /// Makes this console the one displayed, repainting the whole screen.
+    pub fn show(&mut self) {
+        self.visible = true;
+        self.origin = 0;
+        self.hw_set_origin();
+        if self.view_offset == 0 {
+            self.blit();
+        } else {
+            self.render_view();
+        }
+        self.hw_cursor_shape();
+        self.hw_cursor_update();
+    }
+
+    /// Stops mirroring output to VGA memory; contents keep updating off-screen.
+    pub fn hide(&mut self) {
+        self.visible = false;
+    }
+
+    fn blit(&self) {
+        if !self.visible {
+            return;
+        }
+        for (i, &cell) in self.cells[..self.width * self.height].iter().enumerate() {
+            unsafe { self.write_vga(i, cell) };
+        }
     }
 
     fn hw_cursor_update(&self) {
-        let pos = (self.row * WIDTH + self.col) as u16;
-        unsafe {
-            outb(VGA_CRTC_ADDR, 0x0F);
-            outb(VGA_CRTC_DATA, (pos & 0xFF) as u8);
-            outb(VGA_CRTC_ADDR, 0x0E);
-            outb(VGA_CRTC_DATA, (pos >> 8) as u8);
+        if !self.visible {
+            return;
         }
+        let pos = (self.origin + self.row * self.width + self.col) as u16;
+        vga_regs::crtc_write(CRTC_CURSOR_LOC_LOW, (pos & 0xFF) as u8);
+        vga_regs::crtc_write(CRTC_CURSOR_LOC_HIGH, (pos >> 8) as u8);
+    }
+
+    fn hw_cursor_shape(&self) {
+        if !self.visible {
+            return;
+        }
+        let (start, end) = self.cursor_shape.scanlines(text_mode().char_height());
+        vga_regs::set_cursor(start, end, self.cursor_visible);
+    }
+
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) {
+        self.cursor_shape = shape;
+        self.hw_cursor_shape();
+    }
+
+    /// Hides or shows the cursor, e.g. around full-screen redraws.
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+        self.hw_cursor_shape();
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    fn hw_set_origin(&self) {
+        let start = self.origin as u16;
+        vga_regs::crtc_write(CRTC_START_ADDR_LOW, (start & 0xFF) as u8);
+        vga_regs::crtc_write(CRTC_START_ADDR_HIGH, (start >> 8) as u8);
+    }
+
+    /*
+        Scroll the physical screen up one line by moving the CRTC start address
+        one row further into text memory; only the new bottom row is written.
+        When the window would run past the end of text memory, wrap back to
+        offset 0 and repaint the screen once.
+    */
+    fn hw_scroll(&mut self) {
+        let next = self.origin + self.width;
+        if next + self.width * self.height <= VGA_MEM_CELLS {
+            self.origin = next;
+            for c in 0..self.width {
+                let v = self.cells[(self.height - 1) * self.width + c];
+                unsafe { self.write_vga((self.height - 1) * self.width + c, v) };
+            }
+        } else {
+            self.origin = 0;
+            self.blit();
+        }
+        self.hw_set_origin();
     }
 
     fn newline(&mut self) {
         self.col = 0;
-        if self.row < HEIGHT - 1 {
+        if self.row < self.height - 1 {
             self.row += 1;
         } else {
-            for r in 1..HEIGHT {
-                for c in 0..WIDTH {
-                    let v = unsafe { self.read_cell(r, c) };
-                    unsafe { self.write_cell(r - 1, c, v) };
-                }
+            self.push_history(0);
+            let blank = self.blank();
+            let size = self.width * self.height;
+            self.cells.copy_within(self.width..size, 0);
+            self.cells[size - self.width..size].fill(blank);
+            if self.visible && self.view_offset == 0 {
+                self.hw_scroll();
             }
-            self.clear_row(HEIGHT - 1);
             self.hw_cursor_update();
         }
     }
 
-    fn clear_row(&mut self, row: usize) {
-        let blank = ((self.color as u16) << 8) | (b' ' as u16);
-        for col in 0..WIDTH {
-            unsafe { self.write_cell(row, col, blank) };
-        }
+    fn push_history(&mut self, row: usize) {
+        let mut line = [0u16; MAX_WIDTH];
+        let start = row * self.width;
+        line[..self.width].copy_from_slice(&self.cells[start..start + self.width]);
+        self.history[self.hist_next] = line;
+        self.hist_next = (self.hist_next + 1) % SCROLLBACK_LINES;
+        self.hist_len = (self.hist_len + 1).min(SCROLLBACK_LINES);
+    }
+
+    /// Returns the `i`-th oldest line still held in the scrollback.
+    fn history_line(&self, i: usize) -> &[u16; MAX_WIDTH] {
+        let oldest = (self.hist_next + SCROLLBACK_LINES - self.hist_len) % SCROLLBACK_LINES;
+        &self.history[(oldest + i) % SCROLLBACK_LINES]
     }
 
-    pub fn write_bytes(&mut self, bytes: &[u8]) {
-        for &b in bytes {
-            self.write_byte(b);
+    /// Scrolls the view `lines` further back into the history.
+    pub fn scroll_back(&mut self, lines: usize) {
+        let target = (self.view_offset + lines).min(self.hist_len);
+        if target == self.view_offset {
+            return;
         }
+        self.view_offset = target;
+        self.render_view();
     }
 
-    pub fn backspace(&mut self) {
-        if self.col > 0 {
-            self.col -= 1;
-        } else if self.row > 0 {
-            self.row -= 1;
-            self.col = WIDTH - 1;
+    /// Scrolls the view `lines` towards the live screen.
+    pub fn scroll_forward(&mut self, lines: usize) {
+        if self.view_offset == 0 {
+            return;
+        }
+        self.view_offset = self.view_offset.saturating_sub(lines);
+        if self.view_offset == 0 {
+            self.blit();
         } else {
-            self.hw_cursor_update();
+            self.render_view();
+        }
+    }
+
+    /// Returns to the live screen if the view is scrolled back.
+    pub fn snap_to_bottom(&mut self) {
+        if self.view_offset != 0 {
+            self.view_offset = 0;
+            self.blit();
+        }
+    }
+
+    pub fn is_scrolled_back(&self) -> bool {
+        self.view_offset != 0
+    }
+
+    /*
+        Screen row r shows line (hist_len - view_offset + r) of the virtual
+        history+screen buffer; lines past the history come from `cells`.
+    */
+    fn render_view(&self) {
+        if !self.visible {
             return;
         }
-        let blank = ((self.color as u16) << 8) | (b' ' as u16);
-        unsafe {
-            self.write_cell(self.row, self.col, blank);
+        let blank = ((DEFAULT_COLOR as u16) << 8) | b' ' as u16;
+        let first = self.hist_len - self.view_offset;
+        for r in 0..self.height {
+            let line = first + r;
+            for c in 0..self.width {
+                let v = if line < self.hist_len {
+                    self.history_line(line)[c]
+                } else {
+                    self.cells[(line - self.hist_len) * self.width + c]
+                };
+                let v = if v == 0 { blank } else { v };
+                unsafe { self.write_vga(r * self.width + c, v) };
+            }
         }
-        self.hw_cursor_update();
+    }
+
+    fn clear_row(&mut self, row: usize) {
+        self.clear_span(row, 0, self.width);
+    }
+
+    /// Blanks columns `from..to` of `row` with the current color.
+    fn clear_span(&mut self, row: usize, from: usize, to: usize) {
+        let blank = self.blank();
+        for col in from..to {
+            self.write_cell(row, col, blank);
+        }
+    }
+
+    fn put_byte(&mut self, b: u8) {
+        match b {
+            b'\n' | b'\r' => self.execute(b),
+            ch => self.put_glyph(ch),
+        }
+    }
+
+    /// Runs a C0 control; those without a meaning here are dropped.
+    fn execute(&mut self, c: u8) {
+        match c {
+            b'\n' => self.newline(),
+            b'\r' => self.col = 0,
+            _ => {}
+        }
+    }
+
+    fn put_glyph(&mut self, g: u8) {
+        if self.col >= self.width {
+            self.newline();
+        }
+        self.write_cell(self.row, self.col, self.pack(g));
+        self.col += 1;
+    }
+
+    fn apply(&mut self, action: Action) {
+        // Columns are clamped to self.width - 1: a pending wrap is cancelled by any explicit move.
+        let col = self.col.min(self.width - 1);
+        match action {
+            Action::Print(b) => self.put_byte(b),
+            Action::Execute(c) => self.execute(c),
+            Action::Sgr(params) => self.sgr(params.as_slice()),
+            Action::CursorUp(n) => self.row = self.row.saturating_sub(n as usize),
+            Action::CursorDown(n) => self.row = (self.row + n as usize).min(self.height - 1),
+            Action::CursorForward(n) => self.col = (col + n as usize).min(self.width - 1),
+            Action::CursorBack(n) => self.col = col.saturating_sub(n as usize),
+            Action::CursorPosition(row, col) => {
+                self.row = (row as usize - 1).min(self.height - 1);
+                self.col = (col as usize - 1).min(self.width - 1);
+            }
+            Action::EraseDisplay(mode) => match mode {
+                0 => {
+                    self.clear_span(self.row, col, self.width);
+                    for r in self.row + 1..self.height {
+                        self.clear_row(r);
+                    }
+                }
+                1 => {
+                    for r in 0..self.row {
+                        self.clear_row(r);
+                    }
+                    self.clear_span(self.row, 0, col + 1);
+                }
+                _ => {
+                    for r in 0..self.height {
+                        self.clear_row(r);
+                    }
+                }
+            },
+            Action::EraseLine(mode) => match mode {
+                0 => self.clear_span(self.row, col, self.width),
+                1 => self.clear_span(self.row, 0, col + 1),
+                _ => self.clear_row(self.row),
+            },
+            Action::SaveCursor => self.saved = (self.row, self.col, self.color),
+            Action::RestoreCursor => {
+                (self.row, self.col, self.color) = self.saved;
+            }
+            Action::CursorVisible(visible) => self.set_cursor_visible(visible),
+        }
+    }
+
+    fn sgr(&mut self, params: &[u16]) {
+        (self.color, self.bold) = ansi::apply_sgr(self.color, self.bold, params, DEFAULT_COLOR);
     }
 }
 
-/// Safe because we use SpinLock to ensure exclusive access.
+/// Safe because every instance lives behind a lock (see `subsystems::vt`).
 unsafe impl Send for VgaTextConsole {}
 
 impl Console for VgaTextConsole {
+    fn get_color_code(&self) -> u8 {
+        self.color
+    }
+
     fn clear_screen(&mut self) {
-        let blank = ((self.color as u16) << 8) | b' ' as u16;
-        for row in 0..HEIGHT {
-            for col in 0..WIDTH {
-                unsafe { self.write_cell(row, col, blank) };
+        self.snap_to_bottom();
+        let blank = self.blank();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                self.write_cell(row, col, blank);
             }
         }
         self.col = 0;
@@ -149,28 +488,227 @@ impl Console for VgaTextConsole {
     }
 
     fn write_byte(&mut self, b: u8) {
-        match b {
-            b'\n' => self.newline(),
-            ch => {
-                if self.col >= WIDTH {
-                    self.newline();
-                }
-                unsafe {
-                    self.write_cell(self.row, self.col, self.pack(ch));
-                }
-                self.col += 1;
-            }
+        self.snap_to_bottom();
+        if let Some(action) = self.ansi.advance(b) {
+            self.apply(action);
+        }
+        self.hw_cursor_update();
+    }
+
+    fn write_glyph(&mut self, g: u8) {
+        self.snap_to_bottom();
+        self.put_glyph(g);
+        self.hw_cursor_update();
+    }
+
+    fn backspace(&mut self) {
+        self.snap_to_bottom();
+        if self.col > 0 {
+            self.col -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.col = self.width - 1;
+        } else {
+            self.hw_cursor_update();
+            return;
         }
+        let blank = self.blank();
+        self.write_cell(self.row, self.col, blank);
         self.hw_cursor_update();
     }
 }
 
-pub static CONSOLE: SpinLock<VgaTextConsole> = SpinLock::new(VgaTextConsole::new());
+/// Plane 2 font found at boot (the BIOS 8x16 one), kept to derive 8-line glyphs.
+struct RomFont {
+    glyphs: [u8; GLYPHS * GLYPH_STRIDE],
+    captured: bool,
+}
+
+static ROM_FONT: SpinLock<RomFont> = SpinLock::new(RomFont {
+    glyphs: [0; GLYPHS * GLYPH_STRIDE],
+    captured: false,
+});
+
+static MODE: SpinLock<TextMode> = SpinLock::new(TextMode::Text80x25);
+
+fn capture_rom_font(current: TextMode, rom: &mut RomFont) {
+    if !rom.captured && current.char_height() == 16 {
+        vga_regs::read_font(&mut rom.glyphs);
+        rom.captured = true;
+    }
+}
+
+/// Copies the boot font out of video memory while it is still there, so
+/// `set_text_mode` can reinstall it after a graphics mode has cleared it.
+pub fn save_font() {
+    let current = MODE.lock();
+    capture_rom_font(*current, &mut ROM_FONT.lock());
+}
+
+pub fn text_mode() -> TextMode {
+    *MODE.lock()
+}
+
+/*
+    Reprogram the VGA for `mode` and install a matching font. Consoles are not
+    touched: callers resize them afterwards (see `vt::set_text_mode`).
+
+    8-line modes get the boot font squeezed vertically (each pair of
+    scanlines OR-ed together), which keeps every CP437 glyph available
+    without shipping a second font.
+*/
+pub fn set_text_mode(mode: TextMode) {
+    let mut current = MODE.lock();
+    let mut rom = ROM_FONT.lock();
+    capture_rom_font(*current, &mut rom);
+    vga_regs::write_registers(mode.registers());
+    if rom.captured {
+        let glyphs = &rom.glyphs;
+        match mode.char_height() {
+            8 => vga_regs::write_font_with(|c, l| {
+                let base = c * GLYPH_STRIDE + 2 * l;
+                if l < 8 {
+                    glyphs[base] | glyphs[base + 1]
+                } else {
+                    0
+                }
+            }),
+            _ => vga_regs::write_font_with(|c, l| glyphs[c * GLYPH_STRIDE + l]),
+        }
+    }
+    *current = mode;
+}
+
+/// Replaces the font of the current mode; `height` must match its character cell.
+pub fn load_font(glyphs: &[u8], height: usize) -> Result<(), FontError> {
+    if height != text_mode().char_height() {
+        return Err(FontError::BadHeight);
+    }
+    vga_regs::write_font(glyphs, height)
+}
+
+/// Chooses between blinking text and 16 background colors for attribute bit 7.
+pub fn set_blink(enabled: bool) {
+    vga_regs::set_blink(enabled);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+        Never shown, so nothing reaches VGA memory. Too big for a test's
+        stack, hence a static reset by each test.
+    */
+    static CONSOLE: SpinLock<VgaTextConsole> = SpinLock::new(VgaTextConsole::new());
+
+    fn fresh(c: &mut VgaTextConsole) {
+        c.resize(80, 25);
+        c.set_color(vga_color::LIGHT_GRAY, vga_color::BLACK);
+        c.clear_screen();
+        c.hist_next = 0;
+        c.hist_len = 0;
+    }
+
+    fn glyph(c: &VgaTextConsole, row: usize, col: usize) -> u8 {
+        c.cells[row * c.width + col] as u8
+    }
+
+    #[test_case]
+    fn long_lines_wrap_on_the_next_glyph() {
+        let mut c = CONSOLE.lock();
+        fresh(&mut c);
+        for _ in 0..80 {
+            c.write_byte(b'x');
+        }
+        // The wrap is pending until something is actually written past the edge.
+        assert_eq!((c.row, c.col), (0, 80));
+        c.write_byte(b'y');
+        assert_eq!((c.row, c.col), (1, 1));
+        assert_eq!(glyph(&c, 0, 79), b'x');
+        assert_eq!(glyph(&c, 1, 0), b'y');
+    }
+
+    #[test_case]
+    fn newline_on_the_last_row_scrolls_into_history() {
+        let mut c = CONSOLE.lock();
+        fresh(&mut c);
+        c.write_str("first\n");
+        for _ in 0..24 {
+            c.write_byte(b'\n');
+        }
+        assert_eq!(c.row, 24);
+        assert_eq!(c.hist_len, 1);
+        assert_eq!(c.history_line(0)[0] as u8, b'f');
+        assert_eq!(glyph(&c, 0, 0), b' ');
+    }
+
+    #[test_case]
+    fn backspace_at_line_start_goes_to_the_previous_line() {
+        let mut c = CONSOLE.lock();
+        fresh(&mut c);
+        c.write_str("ab\n");
+        c.backspace();
+        assert_eq!((c.row, c.col), (0, 79));
+        c.write_str("\x1b[1;3H");
+        c.backspace();
+        assert_eq!((c.row, c.col), (0, 1));
+        assert_eq!(glyph(&c, 0, 1), b' ');
+    }
+
+    #[test_case]
+    fn controls_inside_a_sequence_are_not_drawn() {
+        let mut c = CONSOLE.lock();
+        fresh(&mut c);
+        c.write_str("ab\x1b[\x07\r2C!");
+        // The bell is dropped and CR runs before the move: "!" lands at column 2.
+        assert_eq!(glyph(&c, 0, 2), b'!');
+        assert_eq!(glyph(&c, 0, 0), b'a');
+        assert_eq!(glyph(&c, 0, 1), b'b');
+    }
+
+    #[test_case]
+    fn sgr_sets_the_cell_color() {
+        let mut c = CONSOLE.lock();
+        fresh(&mut c);
+        c.write_str("\x1b[31;44mr\x1b[0m");
+        let cell = c.cells[0];
+        assert_eq!(
+            (cell >> 8) as u8,
+            color_code(vga_color::RED, vga_color::BLUE)
+        );
+        assert_eq!(c.get_color_code(), DEFAULT_COLOR);
+    }
+
+    #[test_case]
+    fn color_code_puts_the_background_in_the_high_nibble() {
+        assert_eq!(color_code(vga_color::YELLOW, vga_color::BLUE), 0x1E);
+        assert_eq!(color_code(0x1F, 0x2F), 0xFF);
+        assert_eq!(DEFAULT_COLOR, 0x07);
+    }
+
+    #[cfg(not(kernel))]
+    #[test_case]
+    fn shown_console_mirrors_cells_and_cursor_to_the_hardware() {
+        use crate::arch::x86::mock;
 
-pub fn try_with_console<F: FnOnce(&mut VgaTextConsole)>(f: F) {
-    if let Some(mut g) = CONSOLE.try_lock() {
-        f(&mut *g);
-    } else {
-        // drop silently
+        let mut c = CONSOLE.lock();
+        fresh(&mut c);
+        c.show();
+        mock::take_writes();
+        c.write_str("\x1b[31mA");
+        c.hide();
+        let red_a = (color_code(vga_color::RED, vga_color::BLACK) as u16) << 8 | b'A' as u16;
+        assert_eq!(mock::peek::<u16>(VGA_BASE), red_a);
+        assert_eq!(
+            mock::peek::<u16>(VGA_BASE + 2),
+            (DEFAULT_COLOR as u16) << 8 | b' ' as u16
+        );
+        assert!(mock::take_writes().ends_with(&[
+            (0x3D4, CRTC_CURSOR_LOC_LOW as u32),
+            (0x3D5, 1),
+            (0x3D4, CRTC_CURSOR_LOC_HIGH as u32),
+            (0x3D5, 0),
+        ]));
     }
 }
@@ -0,0 +1,137 @@
+/*
+    Kernel entry, from boot.asm's call to `_start_kernel` to the main loop,
+    and the panic handler. Only part of the kernel builds (see lib.rs).
+*/
+use core::panic::PanicInfo;
+
+use crate::{
+    arch,
+    arch::x86::gdt,
+    boot::{cmdline, BootInfo},
+    debug, drivers, info, println,
+    subsystems::console::vga::vga_color,
+    subsystems::console::{self, Console},
+    subsystems::log,
+    subsystems::sched,
+    subsystems::vt,
+    sync::OnceCell,
+};
+
+#[derive(Copy, Clone)]
+struct BootArgs {
+    magic: u32,
+    mbi_addr: u32,
+    info: Option<BootInfo>,
+}
+
+/// Registers handed over by the loader and what they point to, kept across the
+/// switch to our GDT and stack.
+static BOOT_ARGS: OnceCell<BootArgs> = OnceCell::new();
+
+#[no_mangle]
+pub extern "C" fn _start_kernel(magic: u32, mbi_addr: u32) -> ! {
+    // The magic says whether EBX points to a Multiboot 1 or 2 structure.
+    let info = unsafe { BootInfo::new(magic, mbi_addr) };
+    let _ = BOOT_ARGS.set(BootArgs {
+        magic,
+        mbi_addr,
+        info,
+    });
+    gdt::init_with_entry(kernel_entry_post_gdt)
+}
+
+extern "C" fn kernel_entry_post_gdt() -> ! {
+    let args = *BOOT_ARGS
+        .get()
+        .expect("boot args are set before the GDT switch");
+    kernel_main(args)
+}
+
+fn kernel_main(args: BootArgs) -> ! {
+    let info = args.info;
+    log::init();
+    drivers::serial::init();
+    // Before anything that parameters configure, sinks included.
+    if let Some(line) = info.and_then(|info| info.cmdline()) {
+        cmdline::apply(line);
+    }
+    if log::sinks::serial_wanted() {
+        log::add_sink(&log::sinks::SERIAL, true);
+    }
+    // GRUB loaded the sections for this very image and nothing reuses that memory.
+    if let Some(symbols) = info.and_then(|info| unsafe { info.elf_sections()?.symbols() }) {
+        debug::ksyms::set_elf_symbols(symbols);
+        info!("{} ELF symbols from the loader", symbols.len());
+    }
+    arch::x86::idt::init();
+    #[cfg(feature = "gdbstub")]
+    {
+        debug::gdbstub::init();
+        // Give GDB a chance to set breakpoints before anything else runs.
+        debug::gdbstub::breakpoint();
+    }
+    // The loader set this mode up for us and nothing else draws into it.
+    let fb = info.and_then(|info| info.framebuffer());
+    if !fb.is_some_and(|mode| unsafe { console::init_framebuffer(mode) }) {
+        vt::init();
+    }
+    // Everything logged so far only went to dmesg and COM1.
+    if log::sinks::console_wanted() {
+        log::add_sink(&log::sinks::CONSOLE, true);
+    }
+    #[cfg(test)]
+    crate::test_main();
+
+    info!(
+        "boot magic={:#x} mbi={:#x} ({})",
+        args.magic,
+        args.mbi_addr,
+        info.map_or("unknown protocol", |info| info.protocol())
+    );
+    console::with_color(vga_color::LIGHT_GREEN, vga_color::BLACK, || {
+        println!("42");
+    });
+    gdt::print_stack();
+
+    loop {
+        if let Some(ev) = drivers::input::keyboard::poll_event() {
+            if console::handle_hotkey(ev) {
+                continue;
+            }
+            match ev.printable_byte() {
+                // No terminals on a framebuffer: echo straight to the console.
+                Some(0x08) if console::framebuffer_active() => console::backspace(),
+                Some(b) if console::framebuffer_active() => console::write_byte(b),
+                Some(b) => {
+                    vt::push_input(b);
+                }
+                None => {}
+            }
+        }
+        vt::retry_switch();
+        // Echo what was typed on each terminal back onto it.
+        for n in 0..vt::NUM_VTS {
+            vt::drain_input(n, |c, b| {
+                if b == 0x08 {
+                    c.backspace();
+                } else {
+                    c.write_byte(b);
+                }
+            });
+        }
+        // The shell only waits for keys: spawned threads run in between polls.
+        sched::yield_now();
+    }
+}
+
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    debug::panic::panic(info)
+}
+
+#[cfg(test)]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    crate::ktest::panic(info)
+}
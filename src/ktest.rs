@@ -0,0 +1,85 @@
+/*
+    In-kernel test harness (`custom_test_frameworks`).
+
+    `make test` builds the crate with `--test` into a separate kernel, where
+    `kernel_main` runs every `#[test_case]` function instead of the shell
+    loop. Progress goes to COM1; the run ends through QEMU's isa-debug-exit
+    device, with status 33 when all tests passed and 35 at the first failure
+    (a panic).
+
+    `make unit` builds the same tests for the host, where this runner prints
+    to stdout and a failing test unwinds instead: each one runs under
+    `catch_unwind` so the rest still run, and the process exits non-zero.
+*/
+
+#[cfg(kernel)]
+use core::panic::PanicInfo;
+
+#[cfg(kernel)]
+use crate::drivers::qemu::{self, ExitCode};
+#[cfg(kernel)]
+use crate::{serial_print, serial_println};
+
+pub trait Testable {
+    fn run(&self);
+}
+
+#[cfg(kernel)]
+impl<T: Fn()> Testable for T {
+    fn run(&self) {
+        serial_print!("{} ... ", core::any::type_name::<T>());
+        self();
+        serial_println!("ok");
+    }
+}
+
+#[cfg(not(kernel))]
+impl<T: Fn()> Testable for T {
+    fn run(&self) {
+        std::print!("{} ... ", core::any::type_name::<T>());
+        crate::arch::x86::mock::reset();
+        self();
+        std::println!("ok");
+    }
+}
+
+#[cfg(kernel)]
+pub fn runner(tests: &[&dyn Testable]) {
+    serial_println!("running {} tests", tests.len());
+    for test in tests {
+        test.run();
+    }
+    serial_println!("test result: ok. {} passed", tests.len());
+    qemu::exit(ExitCode::Success);
+}
+
+#[cfg(not(kernel))]
+pub fn runner(tests: &[&dyn Testable]) {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    std::println!("running {} tests", tests.len());
+    let mut failed = 0;
+    for test in tests {
+        if catch_unwind(AssertUnwindSafe(|| test.run())).is_err() {
+            std::println!("FAILED");
+            failed += 1;
+        }
+    }
+    if failed == 0 {
+        std::println!("test result: ok. {} passed", tests.len());
+    } else {
+        std::println!(
+            "test result: FAILED. {} passed, {} failed",
+            tests.len() - failed,
+            failed
+        );
+        std::process::exit(1);
+    }
+}
+
+#[cfg(kernel)]
+pub fn panic(info: &PanicInfo) -> ! {
+    // The test may have panicked holding COM1.
+    crate::drivers::serial::_print_unlocked(format_args!("FAILED\n\n{}\n", info));
+    qemu::exit(ExitCode::Failed)
+}
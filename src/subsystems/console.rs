@@ -1,35 +1,177 @@
 use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 pub trait Console {
+    /// Current attribute byte (bg<<4 | fg), in VGA palette indices.
+    fn get_color_code(&self) -> u8;
     fn clear_screen(&mut self);
     fn set_color(&mut self, fg: u8, bg: u8);
     fn write_byte(&mut self, b: u8);
+    /// Draws the font glyph `g` at the cursor, without interpreting it as a control code.
+    fn write_glyph(&mut self, g: u8) {
+        self.write_byte(g);
+    }
     fn write_str(&mut self, s: &str) {
-        for byte in s.bytes() {
-            match byte {
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                _ => self.write_byte(0xfe), // ▮
+        for ch in s.chars() {
+            match ch {
+                // ESC and CR are let through for the ANSI escape sequence interpreter.
+                '\n' | '\r' | '\x1b' => self.write_byte(ch as u8),
+                ' '..='~' => self.write_byte(ch as u8),
+                _ => self.write_glyph(cp437::from_char(ch).unwrap_or(cp437::REPLACEMENT)),
             }
         }
     }
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_byte(b);
+        }
+    }
+    /// Erases the character before the cursor and moves onto it.
+    fn backspace(&mut self);
 }
 
 pub use crate::drivers::video::vga_text as vga;
 
-use vga::try_with_console;
+use crate::drivers::input::keyboard::types::{KeyCode, KeyEvent, Modifiers};
+use crate::drivers::video::bga::{self, ModeError};
+use crate::drivers::video::cp437;
+use crate::drivers::video::framebuffer::{Framebuffer, FramebufferConsole, Mode};
+use crate::sync::irq_spinlock::IrqSpinLock;
+
+use super::vt::{self, try_with_console};
+
+/// Number of `print!` calls dropped because the console was already locked.
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/*
+    Graphical console, set up when the bootloader left a linear framebuffer.
+    Once it exists it takes over kernel output from the VGA text terminals,
+    which are unusable outside text mode.
+*/
+static FB_CONSOLE: IrqSpinLock<Option<FramebufferConsole>> = IrqSpinLock::new(None);
+static FB_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Switches kernel output to a text console drawn on the framebuffer `mode`.
+///
+/// Returns `false` (and keeps the VGA text console) if the mode is unusable.
+///
+/// # Safety
+/// `mode` must describe mapped framebuffer memory that nothing else writes to.
+pub unsafe fn init_framebuffer(mode: Mode) -> bool {
+    let Some(mut console) = Framebuffer::new(mode).and_then(FramebufferConsole::new) else {
+        return false;
+    };
+    console.clear_screen();
+    *FB_CONSOLE.lock() = Some(console);
+    FB_ACTIVE.store(true, Ordering::Release);
+    crate::info!("framebuffer {}x{}x{}", mode.width, mode.height, mode.bpp);
+    true
+}
+
+/// Switches the display to `width`x`height`x`bpp` through the Bochs/QEMU
+/// adapter and redraws kernel output there, starting from a blank screen.
+///
+/// Also works from VGA text mode, which the terminals then stop being shown in.
+/// On failure the previous mode and console are back on screen, blank for a
+/// framebuffer one.
+pub fn set_resolution(width: u16, height: u16, bpp: u8) -> Result<(), ModeError> {
+    // Held across the switch so nobody draws into the old mode meanwhile.
+    let mut slot = FB_CONSOLE.lock();
+    if slot.is_none() {
+        // The graphics mode clears video memory, the font included.
+        vga::save_font();
+    }
+    let previous = bga::state();
+    let mode = match bga::set_mode(width, height, bpp) {
+        Ok(mode) => mode,
+        Err(ModeError::Rejected) => {
+            redraw(&mut slot);
+            return Err(ModeError::Rejected);
+        }
+        Err(e) => return Err(e),
+    };
+    // The adapter's framebuffer is only ever drawn through this console.
+    let Some(mut console) = unsafe { Framebuffer::new(mode) }.and_then(FramebufferConsole::new)
+    else {
+        bga::restore(previous);
+        redraw(&mut slot);
+        return Err(ModeError::Unsupported);
+    };
+    console.clear_screen();
+    *slot = Some(console);
+    FB_ACTIVE.store(true, Ordering::Release);
+    Ok(())
+}
+
+/// Brings back the console in `slot`, or the text terminals without one, after
+/// a failed switch left the previous mode with cleared video memory.
+fn redraw(slot: &mut Option<FramebufferConsole>) {
+    match slot {
+        Some(console) => console.clear_screen(),
+        None => vt::set_text_mode(vga::text_mode()),
+    }
+}
+
+/// Whether kernel output goes to the framebuffer rather than VGA text memory.
+pub fn framebuffer_active() -> bool {
+    FB_ACTIVE.load(Ordering::Acquire)
+}
+
+/// Runs `f` on the console kernel output currently goes to, unless it is locked.
+fn try_with_kernel_console<R>(f: impl FnOnce(&mut dyn Console) -> R) -> Option<R> {
+    if framebuffer_active() {
+        FB_CONSOLE.try_lock()?.as_mut().map(|c| f(c))
+    } else {
+        try_with_console(|c| f(c))
+    }
+}
+
+struct Adaptor<'a, C: Console + ?Sized>(&'a mut C);
+
+impl<'a, C: Console + ?Sized> fmt::Write for Adaptor<'a, C> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s);
+        Ok(())
+    }
+}
+
+/// Report messages lost to lock contention before the next one goes out.
+fn flush_dropped(c: &mut dyn Console) {
+    let n = DROPPED.swap(0, Ordering::Relaxed);
+    if n != 0 {
+        use core::fmt::Write;
+        let _ = writeln!(Adaptor(c), "[console: {} message(s) dropped]", n);
+    }
+}
 
 pub fn _print(args: fmt::Arguments) {
-    try_with_console(|c| {
+    let written = try_with_kernel_console(|c| {
         use core::fmt::Write;
-        struct Adaptor<'a, C: Console>(&'a mut C);
-        impl<'a, C: Console> fmt::Write for Adaptor<'a, C> {
-            fn write_str(&mut self, s: &str) -> fmt::Result {
-                self.0.write_str(s);
-                Ok(())
-            }
-        }
+        flush_dropped(c);
         let _ = Adaptor(c).write_fmt(args);
     });
+    if written.is_none() {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn _print_colored(fg: u8, bg: u8, args: fmt::Arguments) {
+    let written = try_with_kernel_console(|c| {
+        use core::fmt::Write;
+        flush_dropped(c);
+        let old = c.get_color_code();
+        c.set_color(fg, bg);
+        let _ = Adaptor(&mut *c).write_fmt(args);
+        c.set_color(old & 0x0F, (old >> 4) & 0x0F);
+    });
+    if written.is_none() {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns how many messages are currently pending as dropped (not yet reported).
+pub fn dropped_messages() -> usize {
+    DROPPED.load(Ordering::Relaxed)
 }
 
 #[macro_export]
@@ -43,32 +185,126 @@ macro_rules! println {
     ($($arg:tt)*) => { $crate::print!("{}\n", format_args!($($arg)*)) }
 }
 
+/// `print!` with a foreground/background color applied to this message only.
+#[macro_export]
+macro_rules! print_colored {
+    ($fg:expr, $bg:expr, $($arg:tt)*) => {
+        $crate::subsystems::console::_print_colored($fg, $bg, format_args!($($arg)*))
+    }
+}
+
+#[macro_export]
+macro_rules! println_colored {
+    ($fg:expr, $bg:expr) => { $crate::print_colored!($fg, $bg, "\n") };
+    ($fg:expr, $bg:expr, $($arg:tt)*) => {
+        $crate::print_colored!($fg, $bg, "{}\n", format_args!($($arg)*))
+    }
+}
+
 pub fn init() {
-    try_with_console(|c| {
+    try_with_kernel_console(|c| {
         c.set_color(0x07, 0x00);
         c.clear_screen();
     });
 }
 
+/// Sets the kernel console color and blanks the screen with it.
+pub fn clear(fg: u8, bg: u8) {
+    try_with_kernel_console(|c| {
+        c.set_color(fg, bg);
+        c.clear_screen();
+    });
+}
+
+/// Releases the locks of every console kernel output may go to, so that
+/// printing cannot be dropped because of a holder that will never resume.
+///
+/// # Safety
+/// Interrupts must be off and the interrupted holders must never run again:
+/// only for the panic path.
+pub unsafe fn force_unlock() {
+    FB_CONSOLE.force_unlock();
+    vt::force_unlock_all();
+}
+
 pub fn write_byte(b: u8) {
-    try_with_console(|c| c.write_byte(b));
+    try_with_kernel_console(|c| c.write_byte(b));
 }
 
 pub fn write_str_fast(s: &str) {
-    try_with_console(|c| c.write_bytes(s.as_bytes()));
+    try_with_kernel_console(|c| c.write_bytes(s.as_bytes()));
 }
 pub fn backspace() {
-    try_with_console(|c| c.backspace());
+    try_with_kernel_console(|c| c.backspace());
+}
+
+/// Handles console navigation keys: Alt+F1..F6 switch virtual terminals,
+/// Shift+PageUp/PageDown scroll the foreground terminal's history.
+///
+/// Returns `true` when the event was consumed and must not be echoed.
+pub fn handle_hotkey(ev: KeyEvent) -> bool {
+    // Terminals and their scrollback live in VGA text memory.
+    if !ev.pressed || framebuffer_active() {
+        return false;
+    }
+    if ev.mods.contains(Modifiers::ALT) {
+        if let KeyCode::F(n @ 1..) = ev.code {
+            if (n as usize) <= vt::NUM_VTS {
+                // A terminal busy right now is switched to from the main loop.
+                if vt::switch_to(n as usize - 1) == Err(vt::SwitchError::Busy) {
+                    vt::defer_switch(n as usize - 1);
+                }
+                return true;
+            }
+        }
+        return false;
+    }
+    if !ev.mods.contains(Modifiers::SHIFT) {
+        return false;
+    }
+    // Half a screen per keypress, like the Linux VT.
+    match ev.code {
+        KeyCode::PageUp => {
+            vt::try_with_vt(vt::active(), |c| c.scroll_back(c.height() / 2));
+            true
+        }
+        KeyCode::PageDown => {
+            vt::try_with_vt(vt::active(), |c| c.scroll_forward(c.height() / 2));
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Restores the previous console color when dropped.
+///
+/// The console lock is only taken while swapping colors, never across the
+/// guarded scope, so printing while the guard is alive is fine.
+pub struct ColorGuard {
+    prev: Option<u8>,
+}
+
+impl ColorGuard {
+    pub fn new(fg: u8, bg: u8) -> Self {
+        let prev = try_with_kernel_console(|c| {
+            let old = c.get_color_code();
+            c.set_color(fg, bg);
+            old
+        });
+        Self { prev }
+    }
+}
+
+impl Drop for ColorGuard {
+    fn drop(&mut self) {
+        if let Some(old) = self.prev {
+            try_with_kernel_console(|c| c.set_color(old & 0x0F, (old >> 4) & 0x0F));
+        }
+    }
 }
 
 /// Temporarily set color for the duration of `f`, then restore previous color.
 pub fn with_color<F: FnOnce()>(fg: u8, bg: u8, f: F) {
-    try_with_console(|c| {
-        let old = c.get_color_code();
-        c.set_color(fg, bg);
-        f();
-        let old_fg = old & 0x0F;
-        let old_bg = (old >> 4) & 0x0F;
-        c.set_color(old_fg, old_bg);
-    });
+    let _guard = ColorGuard::new(fg, bg);
+    f();
 }
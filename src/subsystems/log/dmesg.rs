@@ -0,0 +1,185 @@
+/*
+    In-memory copy of the log, like Linux's dmesg. It is a static, so it
+    records from the very first message, long before any console exists.
+
+    Lines are stored back to back in a byte ring, each one as its level byte
+    (1..=5, never '\n') followed by the text up to and including '\n'. When
+    a new line does not fit, whole lines are dropped from the old end.
+*/
+
+use core::fmt;
+
+use super::{Level, MAX_LINE};
+use crate::sync::irq_spinlock::IrqSpinLock;
+
+pub const DMESG_SIZE: usize = 16 * 1024;
+
+static DMESG: IrqSpinLock<Ring<DMESG_SIZE>> = IrqSpinLock::new(Ring::new());
+
+pub struct Ring<const N: usize> {
+    buf: [u8; N],
+    /// Start of the oldest line.
+    head: usize,
+    len: usize,
+    /// Lines dropped to make room since boot.
+    lost: usize,
+}
+
+impl<const N: usize> Ring<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            head: 0,
+            len: 0,
+            lost: 0,
+        }
+    }
+
+    fn byte(&self, i: usize) -> u8 {
+        self.buf[(self.head + i) % N]
+    }
+
+    fn drop_oldest(&mut self) {
+        let mut n = 0;
+        while n < self.len {
+            n += 1;
+            if self.byte(n - 1) == b'\n' {
+                break;
+            }
+        }
+        self.head = (self.head + n) % N;
+        self.len -= n;
+        self.lost += 1;
+    }
+
+    /// Appends `line`, which should end with '\n' (one is added otherwise).
+    pub fn push(&mut self, level: Level, line: &str) {
+        let mut cut = line.len().min(MAX_LINE - 1);
+        while !line.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        let text = &line.as_bytes()[..cut];
+        let newline = !text.ends_with(b"\n");
+        let needed = 1 + text.len() + newline as usize;
+        if needed > N {
+            return;
+        }
+        while N - self.len < needed {
+            self.drop_oldest();
+        }
+        let header = [level as u8];
+        let bytes = header.iter().chain(text).chain(newline.then_some(&b'\n'));
+        for &b in bytes {
+            self.buf[(self.head + self.len) % N] = b;
+            self.len += 1;
+        }
+    }
+
+    /// Calls `f` with each stored line, oldest first.
+    pub fn for_each(&self, mut f: impl FnMut(Level, &str)) {
+        let mut i = 0;
+        while i < self.len {
+            let level = Level::from_u8(self.byte(i)).unwrap_or(Level::Info);
+            i += 1;
+            let mut raw = [0u8; MAX_LINE];
+            let mut n = 0;
+            while i < self.len {
+                let b = self.byte(i);
+                i += 1;
+                raw[n] = b;
+                n += 1;
+                if b == b'\n' {
+                    break;
+                }
+            }
+            // Pushed as whole `str`s cut on char boundaries.
+            f(level, core::str::from_utf8(&raw[..n]).unwrap_or("?\n"));
+        }
+    }
+
+    pub fn lost(&self) -> usize {
+        self.lost
+    }
+
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+}
+
+impl<const N: usize> Default for Ring<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(super) fn push(level: Level, line: &str) {
+    DMESG.lock().push(level, line);
+}
+
+/// Calls `f` with every line still in the kernel log, oldest first.
+pub fn for_each(f: impl FnMut(Level, &str)) {
+    DMESG.lock().for_each(f);
+}
+
+/// Writes the whole kernel log to `out`, noting lines lost to wrap-around.
+pub fn dump(out: &mut dyn fmt::Write) -> fmt::Result {
+    let dmesg = DMESG.lock();
+    if dmesg.lost() != 0 {
+        writeln!(out, "[dmesg: {} older line(s) lost]", dmesg.lost())?;
+    }
+    let mut result = Ok(());
+    dmesg.for_each(|_, line| {
+        if result.is_ok() {
+            result = out.write_str(line);
+        }
+    });
+    result
+}
+
+/// Empties the kernel log, like `dmesg -c`.
+pub fn clear() {
+    DMESG.lock().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines<const N: usize>(ring: &Ring<N>) -> [(Option<Level>, [u8; 8]); 4] {
+        let mut out = [(None, [0; 8]); 4];
+        let mut i = 0;
+        ring.for_each(|level, line| {
+            let n = line.len().min(8);
+            out[i].0 = Some(level);
+            out[i].1[..n].copy_from_slice(&line.as_bytes()[..n]);
+            i += 1;
+        });
+        out
+    }
+
+    #[test_case]
+    fn lines_come_back_in_order_with_their_level() {
+        let mut ring = Ring::<64>::new();
+        ring.push(Level::Warn, "one\n");
+        ring.push(Level::Debug, "two");
+        let got = lines(&ring);
+        assert_eq!(got[0], (Some(Level::Warn), *b"one\n\0\0\0\0"));
+        assert_eq!(got[1], (Some(Level::Debug), *b"two\n\0\0\0\0"));
+        assert_eq!(got[2].0, None);
+    }
+
+    #[test_case]
+    fn full_ring_drops_whole_old_lines() {
+        // Every line takes 1 + 5 bytes: two fit, a third evicts the first.
+        let mut ring = Ring::<14>::new();
+        ring.push(Level::Info, "aaaa\n");
+        ring.push(Level::Info, "bbbb\n");
+        ring.push(Level::Error, "cccc\n");
+        let got = lines(&ring);
+        assert_eq!(&got[0].1[..5], b"bbbb\n");
+        assert_eq!(got[1], (Some(Level::Error), *b"cccc\n\0\0\0"));
+        assert_eq!(got[2].0, None);
+        assert_eq!(ring.lost(), 1);
+    }
+}
@@ -0,0 +1,493 @@
+/*
+    Kernel log.
+
+        error!, warn!, info!, debug!, trace!    format!-style, like println!
+
+    Every message that passes the filters is stamped with the time since
+    `init` and the module it comes from, stored in the `dmesg` ring buffer
+    (which exists from the first instruction, before any console), and handed
+    to every registered `Sink`:
+
+        [    0.012345] info  drivers::serial: COM1 at 115200 baud
+
+    Filtering happens twice:
+
+        compile time    `STATIC_MAX_LEVEL`, lowered per module prefix in
+                        `STATIC_FILTERS`; anything above is not even built
+        run time        `set_level` for everything, `set_module_level` to
+                        override it for a module prefix (at most
+                        `MAX_MODULE_LEVELS` of them)
+
+    Module prefixes are written without the crate name ("drivers::video")
+    and match whole path segments.
+
+    Boot parameters: `loglevel=` (a name or 1..5) sets the runtime level,
+    `quiet` lowers it to warnings, `console=` picks the sinks (see `sinks`).
+*/
+
+pub mod dmesg;
+pub mod sinks;
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::arch::x86::tsc;
+use crate::boot::cmdline::{ParamError, ParamValue};
+use crate::kernel_param;
+use crate::sync::irq_spinlock::IrqSpinLock;
+use crate::sync::OnceCell;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[repr(u8)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub const ALL: [Level; 5] = [
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+
+    /// Parses a level name, or its number (1 = error ... 5 = trace).
+    pub fn from_name(s: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|&l| l.name().eq_ignore_ascii_case(s) || s.parse::<u8>() == Ok(l as u8))
+    }
+
+    const fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            1 => Some(Level::Error),
+            2 => Some(Level::Warn),
+            3 => Some(Level::Info),
+            4 => Some(Level::Debug),
+            5 => Some(Level::Trace),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// Most verbose level compiled in anywhere.
+pub const STATIC_MAX_LEVEL: Level = if cfg!(debug_assertions) {
+    Level::Trace
+} else {
+    Level::Debug
+};
+
+/// Lower compile-time limits for noisy module prefixes, e.g. `("sync", Level::Info)`.
+const STATIC_FILTERS: &[(&str, Level)] = &[];
+
+/// Runtime level when nothing else was asked for.
+pub const DEFAULT_LEVEL: Level = Level::Info;
+
+pub const MAX_MODULE_LEVELS: usize = 8;
+
+/// Filter used where no module override applies; 0 turns logging off.
+static LEVEL: AtomicU8 = AtomicU8::new(DEFAULT_LEVEL as u8);
+/// Most verbose of `LEVEL` and all overrides, checked before anything else.
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(DEFAULT_LEVEL as u8);
+static MODULE_LEVELS: IrqSpinLock<[Option<(&'static str, u8)>; MAX_MODULE_LEVELS]> =
+    IrqSpinLock::new([None; MAX_MODULE_LEVELS]);
+
+/// Module path relative to the crate: "kfs::drivers::serial" -> "drivers::serial".
+pub const fn strip_crate(module: &str) -> &str {
+    let b = module.as_bytes();
+    let mut i = 0;
+    while i + 1 < b.len() {
+        if b[i] == b':' && b[i + 1] == b':' {
+            let (_, rest) = b.split_at(i + 2);
+            return match core::str::from_utf8(rest) {
+                Ok(s) => s,
+                Err(_) => module,
+            };
+        }
+        i += 1;
+    }
+    module
+}
+
+/// Whether `prefix` names `module` or one of its ancestors.
+const fn prefix_matches(prefix: &str, module: &str) -> bool {
+    let (p, m) = (prefix.as_bytes(), module.as_bytes());
+    if p.len() > m.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < p.len() {
+        if p[i] != m[i] {
+            return false;
+        }
+        i += 1;
+    }
+    p.len() == m.len() || (m[p.len()] == b':' && m.len() > p.len() + 1 && m[p.len() + 1] == b':')
+}
+
+/// Compile-time limit for `module` (a full `module_path!()`), as a level number.
+pub const fn static_max_level(module: &str) -> u8 {
+    let module = strip_crate(module);
+    let mut max = STATIC_MAX_LEVEL as u8;
+    let mut best = 0;
+    let mut i = 0;
+    while i < STATIC_FILTERS.len() {
+        let (prefix, level) = STATIC_FILTERS[i];
+        if prefix_matches(prefix, module) && prefix.len() >= best {
+            best = prefix.len();
+            max = level as u8;
+        }
+        i += 1;
+    }
+    max
+}
+
+/// Sets the runtime level of every module without an override; `None` silences them.
+pub fn set_level(level: Option<Level>) {
+    LEVEL.store(level.map_or(0, |l| l as u8), Ordering::Relaxed);
+    update_max_level(&*MODULE_LEVELS.lock());
+}
+
+pub fn level() -> Option<Level> {
+    Level::from_u8(LEVEL.load(Ordering::Relaxed))
+}
+
+impl ParamValue for Level {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError> {
+        let value = value.ok_or(ParamError::MissingValue)?;
+        Level::from_name(value).ok_or(ParamError::Invalid("a level name or 1..5"))
+    }
+}
+
+kernel_param!("loglevel", |level: Level| {
+    set_level(Some(level));
+    Ok(())
+});
+
+kernel_param!("quiet", |quiet: bool| {
+    if quiet {
+        set_level(Some(Level::Warn));
+    }
+    Ok(())
+});
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FilterError {
+    /// Already `MAX_MODULE_LEVELS` module overrides.
+    TooManyModules,
+}
+
+/// Overrides the runtime level for `prefix` and the modules under it.
+pub fn set_module_level(prefix: &'static str, level: Option<Level>) -> Result<(), FilterError> {
+    let prefix = relative_prefix(prefix);
+    let value = level.map_or(0, |l| l as u8);
+    let mut table = MODULE_LEVELS.lock();
+    let existing = table
+        .iter()
+        .position(|e| matches!(e, Some((p, _)) if *p == prefix));
+    let slot = existing
+        .or_else(|| table.iter().position(Option::is_none))
+        .ok_or(FilterError::TooManyModules)?;
+    table[slot] = Some((prefix, value));
+    update_max_level(&*table);
+    Ok(())
+}
+
+/// Drops every module override.
+pub fn clear_module_levels() {
+    let mut table = MODULE_LEVELS.lock();
+    *table = [None; MAX_MODULE_LEVELS];
+    update_max_level(&*table);
+}
+
+/// Accepts "kfs::drivers" as well as "drivers".
+fn relative_prefix(prefix: &str) -> &str {
+    prefix
+        .strip_prefix(env!("CARGO_CRATE_NAME"))
+        .and_then(|rest| rest.strip_prefix("::"))
+        .unwrap_or(prefix)
+}
+
+fn update_max_level(table: &[Option<(&'static str, u8)>]) {
+    let max = table
+        .iter()
+        .flatten()
+        .map(|&(_, l)| l)
+        .fold(LEVEL.load(Ordering::Relaxed), u8::max);
+    MAX_LEVEL.store(max, Ordering::Relaxed);
+}
+
+/// Runtime filter for a message at `level` from `module` (a full `module_path!()`).
+pub fn enabled(level: Level, module: &str) -> bool {
+    let level = level as u8;
+    if level > MAX_LEVEL.load(Ordering::Relaxed) {
+        return false;
+    }
+    let module = strip_crate(module);
+    let table = MODULE_LEVELS.lock();
+    let limit = table
+        .iter()
+        .flatten()
+        .filter(|(prefix, _)| prefix_matches(prefix, module))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(LEVEL.load(Ordering::Relaxed), |&(_, l)| l);
+    level <= limit
+}
+
+/// TSC value at `init` and TSC rate, for timestamps (no 64-bit atomics on i386).
+struct Clock {
+    base: u64,
+    hz: u64,
+}
+
+static CLOCK: OnceCell<Clock> = OnceCell::new();
+
+/// Starts the clock used for timestamps. Messages logged earlier show 0.
+pub fn init() {
+    let hz = tsc::calibrate_hz();
+    let _ = CLOCK.set(Clock {
+        base: tsc::read(),
+        hz,
+    });
+    crate::info!("TSC at {}.{:03} MHz", hz / 1_000_000, hz / 1_000 % 1_000);
+}
+
+/// Microseconds since `init`.
+fn timestamp_us() -> u64 {
+    let Some(&Clock { base, hz }) = CLOCK.get() else {
+        return 0;
+    };
+    if hz == 0 {
+        return 0;
+    }
+    let cycles = tsc::read().wrapping_sub(base);
+    // Split so that nothing overflows (or needs 128-bit division on i386).
+    cycles / hz * 1_000_000 + cycles % hz * 1_000_000 / hz
+}
+
+/// Longest line kept, header included; longer messages are cut.
+pub const MAX_LINE: usize = 256;
+
+/// Stack buffer one message is formatted into before it goes anywhere.
+pub struct Line {
+    buf: [u8; MAX_LINE],
+    len: usize,
+}
+
+impl Line {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_LINE],
+            len: 0,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only whole `str`s or their valid prefixes are ever copied in.
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+}
+
+impl Default for Line {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for Line {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let room = MAX_LINE - 1 - self.len; // the last byte is kept for '\n'
+        let mut n = s.len().min(room);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+/// A message as handed to sinks.
+pub struct Record<'a> {
+    pub level: Level,
+    /// Module path without the crate name.
+    pub module: &'a str,
+    pub timestamp_us: u64,
+    /// The whole formatted line, header and trailing newline included.
+    pub line: &'a str,
+}
+
+/// Where log lines go besides `dmesg`.
+pub trait Sink: Sync {
+    fn write(&self, record: &Record);
+}
+
+pub const MAX_SINKS: usize = 4;
+
+/*
+    Taken around the dmesg push in `_log` too, so a sink added with `replay`
+    gets each message exactly once, from the replay or from `_log`.
+*/
+static SINKS: IrqSpinLock<[Option<&'static dyn Sink>; MAX_SINKS]> =
+    IrqSpinLock::new([None; MAX_SINKS]);
+
+/// Starts sending log lines to `sink`, first replaying `dmesg` to it when
+/// `replay` is set, so a console opened late still shows early messages.
+///
+/// Returns `false` if `MAX_SINKS` are already registered.
+pub fn add_sink(sink: &'static dyn Sink, replay: bool) -> bool {
+    let mut sinks = SINKS.lock();
+    let Some(slot) = sinks.iter_mut().find(|s| s.is_none()) else {
+        return false;
+    };
+    *slot = Some(sink);
+    if replay {
+        dmesg::for_each(|level, line| {
+            sink.write(&Record {
+                level,
+                module: "",
+                timestamp_us: 0,
+                line,
+            })
+        });
+    }
+    true
+}
+
+/// Formats and dispatches one message; use the macros instead.
+pub fn _log(level: Level, module: &'static str, args: fmt::Arguments) {
+    let module = strip_crate(module);
+    let timestamp_us = timestamp_us();
+    let mut line = Line::new();
+    let _ = write!(
+        line,
+        "[{:5}.{:06}] {:<5} {}: {}",
+        timestamp_us / 1_000_000,
+        timestamp_us % 1_000_000,
+        level,
+        module,
+        args
+    );
+    line.buf[line.len] = b'\n';
+    line.len += 1;
+
+    let record = Record {
+        level,
+        module,
+        timestamp_us,
+        line: line.as_str(),
+    };
+    let sinks = {
+        let sinks = SINKS.lock();
+        dmesg::push(level, record.line);
+        *sinks
+    };
+    for sink in sinks.iter().flatten() {
+        sink.write(&record);
+    }
+}
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {{
+        const STATIC_MAX: u8 = $crate::subsystems::log::static_max_level(module_path!());
+        let level: $crate::subsystems::log::Level = $level;
+        if level as u8 <= STATIC_MAX && $crate::subsystems::log::enabled(level, module_path!()) {
+            $crate::subsystems::log::_log(level, module_path!(), format_args!($($arg)+));
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::log!($crate::subsystems::log::Level::Error, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => { $crate::log!($crate::subsystems::log::Level::Warn, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::log!($crate::subsystems::log::Level::Info, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::log!($crate::subsystems::log::Level::Debug, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => { $crate::log!($crate::subsystems::log::Level::Trace, $($arg)+) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn levels_parse_by_name_or_number() {
+        assert_eq!(Level::from_name("WARN"), Some(Level::Warn));
+        assert_eq!(Level::from_name("5"), Some(Level::Trace));
+        assert_eq!(Level::from_name("0"), None);
+        assert_eq!(Level::from_name("verbose"), None);
+    }
+
+    #[test_case]
+    fn module_prefixes_match_whole_segments() {
+        assert_eq!(strip_crate("kfs::drivers::serial"), "drivers::serial");
+        assert_eq!(strip_crate("kfs"), "kfs");
+        assert!(prefix_matches("drivers", "drivers::serial"));
+        assert!(prefix_matches("drivers::serial", "drivers::serial"));
+        assert!(!prefix_matches("drivers::ser", "drivers::serial"));
+        assert!(!prefix_matches("drivers::serial::uart", "drivers::serial"));
+    }
+
+    #[test_case]
+    fn module_overrides_beat_the_global_level() {
+        set_level(Some(Level::Warn));
+        set_module_level("kfs::drivers", Some(Level::Debug)).unwrap();
+        set_module_level("drivers::video", None).unwrap();
+        assert!(enabled(Level::Debug, "kfs::drivers::serial"));
+        assert!(!enabled(Level::Trace, "kfs::drivers::serial"));
+        assert!(!enabled(Level::Error, "kfs::drivers::video::bga"));
+        assert!(!enabled(Level::Info, "kfs::sync::spinlock"));
+        assert!(enabled(Level::Warn, "kfs::sync::spinlock"));
+        clear_module_levels();
+        set_level(Some(DEFAULT_LEVEL));
+        assert!(!enabled(Level::Debug, "kfs::drivers::serial"));
+    }
+
+    #[test_case]
+    fn long_lines_are_cut_on_a_char_boundary() {
+        let mut line = Line::new();
+        for _ in 0..MAX_LINE {
+            let _ = line.write_str("é");
+        }
+        assert_eq!(line.len, MAX_LINE - 2);
+        assert!(line.as_str().chars().all(|c| c == 'é'));
+    }
+}
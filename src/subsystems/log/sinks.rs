@@ -0,0 +1,67 @@
+/*
+    The two sinks the kernel has: the active console (VGA text terminal or
+    framebuffer), with errors and warnings in color, and COM1.
+
+    `console=serial,vga` on the command line lists the ones to register;
+    both are by default. It only steers the log, not `print!`.
+*/
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use super::{Level, Record, Sink};
+use crate::boot::cmdline::ParamError;
+use crate::drivers::serial;
+use crate::kernel_param;
+use crate::subsystems::console::{self, vga::vga_color};
+
+pub struct ConsoleSink;
+
+pub struct SerialSink;
+
+pub static CONSOLE: ConsoleSink = ConsoleSink;
+pub static SERIAL: SerialSink = SerialSink;
+
+const WANT_SERIAL: u8 = 1 << 0;
+const WANT_CONSOLE: u8 = 1 << 1;
+
+static WANTED: AtomicU8 = AtomicU8::new(WANT_SERIAL | WANT_CONSOLE);
+
+kernel_param!("console", |names: &'static str| {
+    let mut wanted = 0;
+    for name in names.split(',') {
+        wanted |= match name {
+            "serial" => WANT_SERIAL,
+            "vga" => WANT_CONSOLE,
+            _ => return Err(ParamError::Invalid("serial and/or vga")),
+        };
+    }
+    WANTED.store(wanted, Ordering::Relaxed);
+    Ok(())
+});
+
+/// Whether `console=` left COM1 in.
+pub fn serial_wanted() -> bool {
+    WANTED.load(Ordering::Relaxed) & WANT_SERIAL != 0
+}
+
+/// Whether `console=` left the screen in.
+pub fn console_wanted() -> bool {
+    WANTED.load(Ordering::Relaxed) & WANT_CONSOLE != 0
+}
+
+impl Sink for ConsoleSink {
+    fn write(&self, record: &Record) {
+        let args = format_args!("{}", record.line);
+        match record.level {
+            Level::Error => console::_print_colored(vga_color::LIGHT_RED, vga_color::BLACK, args),
+            Level::Warn => console::_print_colored(vga_color::YELLOW, vga_color::BLACK, args),
+            _ => console::_print(args),
+        }
+    }
+}
+
+impl Sink for SerialSink {
+    fn write(&self, record: &Record) {
+        serial::_print(format_args!("{}", record.line));
+    }
+}
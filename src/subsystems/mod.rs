@@ -1 +1,4 @@
 pub mod console;
+pub mod log;
+pub mod sched;
+pub mod vt;
@@ -0,0 +1,288 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::arch::x86::{context, interrupts};
+use crate::sync::irq;
+use crate::sync::irq_spinlock::IrqSpinLock;
+
+/*
+    Cooperative kernel threads.
+
+    A thread runs until it yields, blocks or returns; there is no timer
+    preemption yet. Thread 0 is the boot thread (kernel_main on the boot
+    stack); spawned threads each get one of the fixed stacks below.
+
+    Switching happens with interrupts disabled. The IRQ-disable nesting state
+    of `sync::irq` is saved on the switching thread's stack and restored when
+    it resumes, so blocking inside an `IrqDisabled` section is fine.
+
+    Only threads wake each other for now: the IDT has no IRQ gates and the PIC
+    is not remapped, so no interrupt handler can call `wake`. Every thread
+    blocked at once is therefore a deadlock, and `schedule` panics instead of
+    halting until an interrupt that cannot come.
+*/
+
+/// Maximum number of threads alive at once, the boot thread included.
+pub const MAX_THREADS: usize = 8;
+
+const STACK_SIZE: usize = 16 * 1024;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct ThreadId(usize);
+
+impl ThreadId {
+    pub const BOOT: ThreadId = ThreadId(0);
+
+    pub fn as_usize(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ThreadState {
+    Free,
+    Ready,
+    Running,
+    /// Waiting for `wake`.
+    Blocked,
+    /// Returned from its entry point; the slot is reused by the next `spawn`.
+    Exited,
+}
+
+#[derive(Copy, Clone)]
+struct Thread {
+    state: ThreadState,
+    esp: usize, // saved stack pointer while switched out
+    entry: Option<fn()>,
+    name: &'static str,
+    start_flags: u32, // EFLAGS on first run (IF inherited from the spawner)
+}
+
+impl Thread {
+    const FREE: Thread = Thread {
+        state: ThreadState::Free,
+        esp: 0,
+        entry: None,
+        name: "",
+        start_flags: 0,
+    };
+}
+
+struct Scheduler {
+    threads: [Thread; MAX_THREADS],
+    current: usize,
+}
+
+impl Scheduler {
+    /// Next ready thread after the current one, round-robin (the current one last).
+    fn pick_next(&self) -> Option<usize> {
+        (1..=MAX_THREADS)
+            .map(|i| (self.current + i) % MAX_THREADS)
+            .find(|&i| self.threads[i].state == ThreadState::Ready)
+    }
+}
+
+static SCHED: IrqSpinLock<Scheduler> = IrqSpinLock::new(Scheduler {
+    threads: {
+        let mut threads = [Thread::FREE; MAX_THREADS];
+        threads[0].state = ThreadState::Running;
+        threads[0].name = "boot";
+        threads
+    },
+    current: 0,
+});
+
+#[repr(align(16))]
+struct Stack(UnsafeCell<[u8; STACK_SIZE]>);
+
+// Each stack is only used by the thread occupying the matching slot.
+unsafe impl Sync for Stack {}
+
+/// Stacks of threads 1..MAX_THREADS (the boot thread keeps the boot stack).
+static STACKS: [Stack; MAX_THREADS - 1] =
+    [const { Stack(UnsafeCell::new([0; STACK_SIZE])) }; MAX_THREADS - 1];
+
+/// Mirror of `Scheduler::current`, readable without taking SCHED (lock tracking needs it).
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+
+pub fn current() -> ThreadId {
+    ThreadId(CURRENT.load(Ordering::Relaxed))
+}
+
+pub fn state(id: ThreadId) -> Option<ThreadState> {
+    SCHED.lock().threads.get(id.0).map(|t| t.state)
+}
+
+pub fn name(id: ThreadId) -> Option<&'static str> {
+    SCHED.lock().threads.get(id.0).map(|t| t.name)
+}
+
+/// Creates a thread running `entry`; it first runs when another thread yields or blocks.
+///
+/// Returns `None` when all `MAX_THREADS` slots are taken.
+pub fn spawn(name: &'static str, entry: fn()) -> Option<ThreadId> {
+    let flags = interrupts::read_eflags();
+    let mut s = SCHED.lock();
+    let id = (1..MAX_THREADS)
+        .find(|&i| matches!(s.threads[i].state, ThreadState::Free | ThreadState::Exited))?;
+    let esp = unsafe {
+        let top = (STACKS[id - 1].0.get() as *mut u8).add(STACK_SIZE);
+        context::init_stack(top, thread_start)
+    };
+    s.threads[id] = Thread {
+        state: ThreadState::Ready,
+        esp,
+        entry: Some(entry),
+        name,
+        start_flags: flags,
+    };
+    Some(ThreadId(id))
+}
+
+/// First code run by every spawned thread, entered from `context::switch_context`.
+extern "C" fn thread_start() -> ! {
+    let (entry, flags) = {
+        let s = SCHED.lock();
+        let t = &s.threads[s.current];
+        (t.entry, t.start_flags)
+    };
+    interrupts::restore(flags);
+    if let Some(entry) = entry {
+        entry();
+    }
+    exit()
+}
+
+/// Ends the current thread.
+pub fn exit() -> ! {
+    {
+        let mut s = SCHED.lock();
+        let cur = s.current;
+        s.threads[cur].state = ThreadState::Exited;
+    }
+    schedule();
+    unreachable!("exited thread was scheduled again");
+}
+
+/// Lets the other ready threads run before coming back.
+pub fn yield_now() {
+    schedule();
+}
+
+/// Puts the current thread to sleep until `wake` is called on it.
+///
+/// To avoid missing a wakeup, keep interrupts disabled from the moment the
+/// thread is registered with whoever will wake it until this call.
+pub fn block_current() {
+    {
+        let mut s = SCHED.lock();
+        let cur = s.current;
+        s.threads[cur].state = ThreadState::Blocked;
+    }
+    schedule();
+}
+
+/// Makes a blocked thread runnable again. Safe to call from interrupt handlers,
+/// once there are any (see the top of this file).
+///
+/// Returns `false` if the thread was not blocked.
+pub fn wake(id: ThreadId) -> bool {
+    let mut s = SCHED.lock();
+    match s.threads.get_mut(id.0) {
+        Some(t) if t.state == ThreadState::Blocked => {
+            t.state = ThreadState::Ready;
+            true
+        }
+        _ => false,
+    }
+}
+
+fn schedule() {
+    let flags = interrupts::save_and_disable();
+    let irq_state = irq::take_state();
+    let mut s = SCHED.lock();
+    let cur = s.current;
+    if s.threads[cur].state == ThreadState::Running {
+        s.threads[cur].state = ThreadState::Ready;
+    }
+    let Some(next) = s.pick_next() else {
+        drop(s);
+        // Nothing runnable, and no interrupt to wait for (see the top of this file).
+        panic!("sched: every thread is blocked");
+    };
+    s.threads[next].state = ThreadState::Running;
+    if next != cur {
+        s.current = next;
+        CURRENT.store(next, Ordering::Relaxed);
+        let old_esp = &mut s.threads[cur].esp as *mut usize;
+        let new_esp = s.threads[next].esp;
+        drop(s);
+        // SCHED is static and interrupts are off: old_esp stays valid and unshared.
+        unsafe { context::switch_context(old_esp, new_esp) };
+    } else {
+        drop(s);
+    }
+    // Back on this thread's stack, possibly much later.
+    irq::set_state(irq_state);
+    interrupts::restore(flags);
+}
+
+#[cfg(all(test, kernel))]
+pub(crate) mod tests {
+    use core::sync::atomic::AtomicBool;
+
+    use super::*;
+    use crate::sync::spinlock::SpinLock;
+
+    /// Yields until every thread in `ids` has returned.
+    pub(crate) fn join(ids: &[ThreadId]) {
+        while ids.iter().any(|&id| state(id) != Some(ThreadState::Exited)) {
+            yield_now();
+        }
+    }
+
+    #[test_case]
+    fn yield_runs_every_ready_thread_in_turn() {
+        static ORDER: SpinLock<([u8; 4], usize)> = SpinLock::new(([0; 4], 0));
+        fn record(b: u8) {
+            let mut order = ORDER.lock();
+            let n = order.1;
+            order.0[n] = b;
+            order.1 += 1;
+        }
+        fn a() {
+            record(b'a');
+            yield_now();
+            record(b'a');
+        }
+        fn b() {
+            record(b'b');
+            yield_now();
+            record(b'b');
+        }
+        let ids = [spawn("a", a).unwrap(), spawn("b", b).unwrap()];
+        // Nothing runs before the spawner lets go of the CPU.
+        assert_eq!(ORDER.lock().1, 0);
+        join(&ids);
+        assert_eq!(&ORDER.lock().0, b"abab");
+    }
+
+    #[test_case]
+    fn wake_only_succeeds_on_a_blocked_thread() {
+        static WOKEN: AtomicBool = AtomicBool::new(false);
+        fn sleeper() {
+            block_current();
+            WOKEN.store(true, Ordering::Relaxed);
+        }
+        assert!(!wake(current()));
+        let id = spawn("sleeper", sleeper).unwrap();
+        assert!(!wake(id));
+        yield_now();
+        assert_eq!(state(id), Some(ThreadState::Blocked));
+        assert!(!WOKEN.load(Ordering::Relaxed));
+        assert!(wake(id));
+        assert!(!wake(id));
+        join(&[id]);
+        assert!(WOKEN.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,258 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::boot::cmdline::{ParamError, ParamValue};
+use crate::drivers::video::vga_regs::TextMode;
+use crate::drivers::video::vga_text::{self, VgaTextConsole};
+use crate::kernel_param;
+use crate::sync::irq_spinlock::IrqSpinLock;
+
+/// Number of virtual terminals, reachable with Alt+F1..Alt+F6.
+pub const NUM_VTS: usize = 6;
+
+/// Terminal that receives kernel output (`print!`/`println!`).
+pub const KLOG_VT: usize = 0;
+
+/// Capacity of each terminal's pending input queue, in bytes.
+const INPUT_QUEUE_LEN: usize = 64;
+
+/*
+    Bytes typed while a terminal is in the foreground, waiting to be consumed.
+    Single producer (keyboard polling), single consumer (whoever owns the VT).
+*/
+struct InputQueue {
+    buf: [u8; INPUT_QUEUE_LEN],
+    head: usize,
+    len: usize,
+}
+
+impl InputQueue {
+    const fn new() -> Self {
+        Self {
+            buf: [0; INPUT_QUEUE_LEN],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Queues a byte, dropping it if the queue is full.
+    fn push(&mut self, b: u8) -> bool {
+        if self.len == INPUT_QUEUE_LEN {
+            return false;
+        }
+        self.buf[(self.head + self.len) % INPUT_QUEUE_LEN] = b;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let b = self.buf[self.head];
+        self.head = (self.head + 1) % INPUT_QUEUE_LEN;
+        self.len -= 1;
+        Some(b)
+    }
+}
+
+/// One virtual terminal: an off-screen text console plus its keyboard input.
+pub struct VirtualTerminal {
+    console: VgaTextConsole,
+    input: InputQueue,
+}
+
+impl VirtualTerminal {
+    const fn new() -> Self {
+        Self {
+            console: VgaTextConsole::new(),
+            input: InputQueue::new(),
+        }
+    }
+}
+
+static VTS: [IrqSpinLock<VirtualTerminal>; NUM_VTS] =
+    [const { IrqSpinLock::new(VirtualTerminal::new()) }; NUM_VTS];
+
+/// Index of the terminal currently shown on screen.
+static ACTIVE: AtomicUsize = AtomicUsize::new(KLOG_VT);
+
+/// Mode asked for with `vga.mode=`, switched to by `init`.
+static BOOT_MODE: IrqSpinLock<Option<TextMode>> = IrqSpinLock::new(None);
+
+/// "80x50" style, columns then rows.
+impl ParamValue for TextMode {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError> {
+        const EXPECTED: &str = "80x25, 80x50 or 90x60";
+        let (cols, rows) = value
+            .ok_or(ParamError::MissingValue)?
+            .split_once('x')
+            .ok_or(ParamError::Invalid(EXPECTED))?;
+        let dims = (cols.parse(), rows.parse());
+        TextMode::ALL
+            .into_iter()
+            .find(|mode| {
+                let (w, h) = mode.dims();
+                dims == (Ok(w), Ok(h))
+            })
+            .ok_or(ParamError::Invalid(EXPECTED))
+    }
+}
+
+kernel_param!("vga.mode", |mode: TextMode| {
+    *BOOT_MODE.lock() = Some(mode);
+    Ok(())
+});
+
+/// Puts the initial terminal on screen, in the `vga.mode=` mode if one was
+/// given. Output written before this is kept and shown.
+pub fn init() {
+    let mode = BOOT_MODE.lock().take();
+    match mode {
+        Some(mode) => set_text_mode(mode),
+        None => VTS[active()].lock().console.show(),
+    }
+}
+
+pub fn active() -> usize {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SwitchError {
+    /// There is no terminal `n`.
+    OutOfRange,
+    /// One of the terminals involved is locked; nothing changed.
+    Busy,
+}
+
+/// Brings terminal `n` to the foreground.
+pub fn switch_to(n: usize) -> Result<(), SwitchError> {
+    if n >= NUM_VTS {
+        return Err(SwitchError::OutOfRange);
+    }
+    let prev = active();
+    if prev == n {
+        return Ok(());
+    }
+    let (Some(mut from), Some(mut to)) = (VTS[prev].try_lock(), VTS[n].try_lock()) else {
+        return Err(SwitchError::Busy);
+    };
+    from.console.hide();
+    ACTIVE.store(n, Ordering::Relaxed);
+    to.console.show();
+    Ok(())
+}
+
+/// Terminal a `Busy` switch still has to bring up, `NUM_VTS` for none.
+static PENDING_SWITCH: AtomicUsize = AtomicUsize::new(NUM_VTS);
+
+/// Remembers a switch that found a terminal locked, for `retry_switch`.
+/// A later request replaces an earlier one, like a second keypress would.
+pub fn defer_switch(n: usize) {
+    if n < NUM_VTS {
+        PENDING_SWITCH.store(n, Ordering::Relaxed);
+    }
+}
+
+/// Attempts the deferred switch, if any; it stays pending while still `Busy`.
+pub fn retry_switch() {
+    let n = PENDING_SWITCH.load(Ordering::Relaxed);
+    if n < NUM_VTS && switch_to(n) != Err(SwitchError::Busy) {
+        let _ = PENDING_SWITCH.compare_exchange(n, NUM_VTS, Ordering::Relaxed, Ordering::Relaxed);
+    }
+}
+
+/// Switches the VGA to `mode` and resizes every terminal to match.
+pub fn set_text_mode(mode: TextMode) {
+    let (width, height) = mode.dims();
+    for vt in VTS.iter() {
+        vt.lock().console.hide();
+    }
+    vga_text::set_text_mode(mode);
+    for vt in VTS.iter() {
+        vt.lock().console.resize(width, height);
+    }
+    VTS[active()].lock().console.show();
+}
+
+/// Releases every terminal lock, for the panic path.
+///
+/// # Safety
+/// See `SpinLock::force_unlock`.
+pub unsafe fn force_unlock_all() {
+    for vt in VTS.iter() {
+        vt.force_unlock();
+    }
+}
+
+/// Runs `f` on terminal `n`'s console, or returns `None` if it is already locked.
+pub fn try_with_vt<R, F: FnOnce(&mut VgaTextConsole) -> R>(n: usize, f: F) -> Option<R> {
+    VTS.get(n)?.try_lock().map(|mut vt| f(&mut vt.console))
+}
+
+/// Runs `f` on the kernel log console, or returns `None` if it is already locked.
+pub fn try_with_console<R, F: FnOnce(&mut VgaTextConsole) -> R>(f: F) -> Option<R> {
+    try_with_vt(KLOG_VT, f)
+}
+
+/// Queues a typed byte for the foreground terminal. Returns `false` if it was dropped.
+pub fn push_input(b: u8) -> bool {
+    VTS[active()].lock().input.push(b)
+}
+
+/// Takes the next pending input byte of terminal `n`.
+pub fn read_input(n: usize) -> Option<u8> {
+    VTS.get(n)?.lock().input.pop()
+}
+
+/// Hands each pending input byte of terminal `n` to `f`, with the terminal's
+/// console. If the terminal is locked, the bytes stay queued for a later call.
+pub fn drain_input(n: usize, mut f: impl FnMut(&mut VgaTextConsole, u8)) {
+    let Some(mut vt) = VTS.get(n).and_then(|vt| vt.try_lock()) else {
+        return;
+    };
+    let vt = &mut *vt;
+    while let Some(b) = vt.input.pop() {
+        f(&mut vt.console, b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The last terminal: never the active one in these tests.
+    const VT: usize = NUM_VTS - 1;
+
+    #[test_case]
+    fn locked_terminal_keeps_its_input() {
+        while read_input(VT).is_some() {}
+        VTS[VT].lock().input.push(b'a');
+        {
+            let _held = VTS[VT].lock();
+            drain_input(VT, |_, _| panic!("the terminal is locked"));
+        }
+        let mut seen = None;
+        drain_input(VT, |_, b| seen = Some(b));
+        assert_eq!(seen, Some(b'a'));
+        assert_eq!(read_input(VT), None);
+    }
+
+    #[test_case]
+    fn busy_switch_is_reported_and_retried() {
+        assert_eq!(switch_to(NUM_VTS), Err(SwitchError::OutOfRange));
+        let prev = active();
+        {
+            let _held = VTS[VT].lock();
+            assert_eq!(switch_to(VT), Err(SwitchError::Busy));
+            defer_switch(VT);
+            retry_switch();
+            assert_eq!(active(), prev);
+        }
+        retry_switch();
+        assert_eq!(active(), VT);
+        // Nothing left pending, and the console goes back where it was.
+        assert_eq!(PENDING_SWITCH.load(Ordering::Relaxed), NUM_VTS);
+        assert_eq!(switch_to(prev), Ok(()));
+    }
+}
@@ -0,0 +1,86 @@
+use super::mutex::MutexGuard;
+use super::wait_queue::WaitQueue;
+
+/// Condition variable used together with a `Mutex`.
+/// - `wait` releases the mutex and sleeps in one step, so a `notify_*` issued
+///   after the caller checked its condition is never missed.
+/// - Wakeups may be spurious: always re-check the condition (or use `wait_while`).
+pub struct Condvar {
+    waiters: WaitQueue,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// Unlocks `guard`'s mutex, sleeps until notified, then locks it again.
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let mutex = guard.mutex;
+        self.waiters.sleep_after(|| drop(guard));
+        mutex.lock()
+    }
+
+    /// Waits for as long as `condition` holds on the protected data.
+    pub fn wait_while<'a, T>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: impl FnMut(&mut T) -> bool,
+    ) -> MutexGuard<'a, T> {
+        while condition(&mut guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    /// Wakes one waiting thread. May be called from interrupt handlers.
+    pub fn notify_one(&self) -> bool {
+        self.waiters.wake_one()
+    }
+
+    /// Wakes every waiting thread. May be called from interrupt handlers.
+    pub fn notify_all(&self) -> usize {
+        self.waiters.wake_all()
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, kernel))]
+mod tests {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::subsystems::sched::{self, tests::join, ThreadState};
+    use crate::sync::Mutex;
+
+    #[test_case]
+    fn wait_while_sleeps_until_the_condition_clears() {
+        static READY: Mutex<bool> = Mutex::new(false);
+        static CHANGED: Condvar = Condvar::new();
+        static DONE: AtomicBool = AtomicBool::new(false);
+        fn waiter() {
+            let ready = CHANGED.wait_while(READY.lock(), |ready| !*ready);
+            assert!(*ready);
+            DONE.store(true, Ordering::Relaxed);
+        }
+        assert!(!CHANGED.notify_one());
+        let id = sched::spawn("waiter", waiter).unwrap();
+        sched::yield_now();
+        assert_eq!(sched::state(id), Some(ThreadState::Blocked));
+        // Woken with the condition unchanged: back to sleep.
+        assert!(CHANGED.notify_one());
+        sched::yield_now();
+        assert_eq!(sched::state(id), Some(ThreadState::Blocked));
+        *READY.lock() = true;
+        assert!(CHANGED.notify_one());
+        join(&[id]);
+        assert!(DONE.load(Ordering::Relaxed));
+    }
+}
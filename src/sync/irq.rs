@@ -0,0 +1,80 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::arch::x86::interrupts;
+
+/*
+    Nested interrupt disabling (single CPU).
+
+    Each `IrqDisabled` guard disables interrupts; only the outermost one
+    remembers whether IF was set and restores it when dropped. Inner guards
+    just count, so a section that disables interrupts can call code that does
+    the same without turning them back on too early.
+*/
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+static WAS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// RAII guard keeping maskable interrupts off until dropped.
+pub struct IrqDisabled {
+    // Must be dropped where it was created: IF is a per-CPU, per-context state.
+    _not_send: core::marker::PhantomData<*const ()>,
+}
+
+impl IrqDisabled {
+    /// Disables interrupts. Not a `Default`: creating the guard has an effect,
+    /// and dropping it on the spot turns interrupts back on.
+    #[allow(clippy::new_without_default)]
+    #[must_use = "interrupts are enabled again as soon as the guard is dropped"]
+    pub fn new() -> Self {
+        let flags = interrupts::save_and_disable();
+        // Interrupts are off from here on, so nothing can race on DEPTH.
+        if DEPTH.fetch_add(1, Ordering::Relaxed) == 0 {
+            WAS_ENABLED.store(interrupts::flags_enabled(flags), Ordering::Relaxed);
+        }
+        Self {
+            _not_send: core::marker::PhantomData,
+        }
+    }
+}
+
+impl Drop for IrqDisabled {
+    fn drop(&mut self) {
+        debug_assert!(
+            !interrupts::are_enabled(),
+            "interrupts enabled inside IrqDisabled"
+        );
+        let prev = DEPTH.fetch_sub(1, Ordering::Relaxed);
+        debug_assert!(prev != 0, "unbalanced IrqDisabled drop");
+        if prev == 1 && WAS_ENABLED.load(Ordering::Relaxed) {
+            // Outermost guard and IF was set when it was taken.
+            unsafe { interrupts::enable() };
+        }
+    }
+}
+
+/// Current nesting depth of `IrqDisabled` guards (0 = none held).
+pub fn disable_depth() -> usize {
+    DEPTH.load(Ordering::Relaxed)
+}
+
+/*
+    Per-thread view of the nesting state: the scheduler swaps it on every
+    context switch so a thread blocked inside a guard does not leave its depth
+    behind for the next one. Only call with interrupts disabled.
+*/
+pub(crate) fn take_state() -> (usize, bool) {
+    (
+        DEPTH.swap(0, Ordering::Relaxed),
+        WAS_ENABLED.swap(false, Ordering::Relaxed),
+    )
+}
+
+pub(crate) fn set_state((depth, was_enabled): (usize, bool)) {
+    DEPTH.store(depth, Ordering::Relaxed);
+    WAS_ENABLED.store(was_enabled, Ordering::Relaxed);
+}
+
+/// Run `f` with interrupts disabled.
+pub fn without_interrupts<R>(f: impl FnOnce() -> R) -> R {
+    let _irq = IrqDisabled::new();
+    f()
+}
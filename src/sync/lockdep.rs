@@ -0,0 +1,339 @@
+/*
+    Lock-order validator ("lockdep"), compiled in with the `lockdep` feature.
+
+    Every lock is its own class, keyed by its address (kernel locks are
+    statics). For each thread the validator keeps the stack of held locks and,
+    globally, a graph with an edge A -> B whenever B was acquired while A was
+    held. Before a blocking acquisition it reports:
+
+        - recursive locking: the lock is already held by this thread. Read
+          locks included: they prefer writers, so a second `read` waits
+          behind any writer queued after the first one;
+        - inverted order: the graph already has a path from the new lock to
+          one that is held, so two threads taking them could deadlock.
+
+    Reports go straight to COM1 (the console may be the lock involved), with
+    the source location of both acquisitions. Tracking stops after the first
+    report. Without the feature every hook below is an empty inline function.
+*/
+
+/// How a lock is being taken.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Kind {
+    /// Exclusive acquisition (`lock`, `write`).
+    Exclusive,
+    /// Shared acquisition (`read`); other threads may hold it too.
+    Shared,
+}
+
+#[cfg(feature = "lockdep")]
+pub use imp::{acquire, acquired_try, release};
+
+#[cfg(not(feature = "lockdep"))]
+#[inline(always)]
+pub fn acquire(_key: usize, _kind: Kind) {}
+
+#[cfg(not(feature = "lockdep"))]
+#[inline(always)]
+pub fn acquired_try(_key: usize, _kind: Kind) {}
+
+#[cfg(not(feature = "lockdep"))]
+#[inline(always)]
+pub fn release(_key: usize) {}
+
+#[cfg(feature = "lockdep")]
+mod imp {
+    use core::cell::UnsafeCell;
+    use core::panic::Location;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use super::Kind;
+    use crate::drivers::serial::_print_unlocked;
+    use crate::subsystems::sched::{self, MAX_THREADS};
+
+    const MAX_CLASSES: usize = 64;
+    const MAX_HELD: usize = 16;
+
+    type Site = &'static Location<'static>;
+
+    #[derive(Copy, Clone)]
+    struct Held {
+        class: usize,
+        kind: Kind,
+        site: Site,
+    }
+
+    struct State {
+        keys: [usize; MAX_CLASSES],
+        nclasses: usize,
+        // deps[a] bit b: b was acquired while a was held.
+        deps: [u64; MAX_CLASSES],
+        // Where the first a -> b edge was seen: (a acquired at, b acquired at).
+        dep_sites: [[Option<(Site, Site)>; MAX_CLASSES]; MAX_CLASSES],
+        held: [[Option<Held>; MAX_HELD]; MAX_THREADS],
+        held_len: [usize; MAX_THREADS],
+        disabled: bool,
+    }
+
+    struct Global(UnsafeCell<State>);
+
+    // Only touched while BUSY is held.
+    unsafe impl Sync for Global {}
+
+    static STATE: Global = Global(UnsafeCell::new(State {
+        keys: [0; MAX_CLASSES],
+        nclasses: 0,
+        deps: [0; MAX_CLASSES],
+        dep_sites: [[None; MAX_CLASSES]; MAX_CLASSES],
+        held: [[None; MAX_HELD]; MAX_THREADS],
+        held_len: [0; MAX_THREADS],
+        disabled: false,
+    }));
+
+    /*
+        Guards STATE. Not a lock of ours (it would be tracked too): when it is
+        already taken - lockdep re-entered from an interrupt handler, or the
+        serial port used while reporting - the event is simply not tracked.
+    */
+    static BUSY: AtomicBool = AtomicBool::new(false);
+
+    fn with_state(f: impl FnOnce(&mut State)) {
+        if BUSY
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return;
+        }
+        let st = unsafe { &mut *STATE.0.get() };
+        if !st.disabled {
+            f(st);
+        }
+        BUSY.store(false, Ordering::Release);
+    }
+
+    macro_rules! report {
+        ($($arg:tt)*) => { _print_unlocked(format_args!("{}\n", format_args!($($arg)*))) }
+    }
+
+    impl State {
+        fn class_of(&mut self, key: usize) -> Option<usize> {
+            if let Some(c) = self.keys[..self.nclasses].iter().position(|&k| k == key) {
+                return Some(c);
+            }
+            if self.nclasses == MAX_CLASSES {
+                report!("lockdep: more than {} locks, turning off", MAX_CLASSES);
+                self.disabled = true;
+                return None;
+            }
+            self.keys[self.nclasses] = key;
+            self.nclasses += 1;
+            Some(self.nclasses - 1)
+        }
+
+        /// Path from `from` to `to` in the dependency graph, as a parent table.
+        fn find_path(&self, from: usize, to: usize) -> Option<[usize; MAX_CLASSES]> {
+            let mut parent = [usize::MAX; MAX_CLASSES];
+            let mut seen: u64 = 1 << from;
+            let mut queue = [0usize; MAX_CLASSES];
+            let (mut head, mut tail) = (0, 1);
+            queue[0] = from;
+            while head < tail {
+                let a = queue[head];
+                head += 1;
+                if a == to {
+                    return Some(parent);
+                }
+                let mut next = self.deps[a] & !seen;
+                while next != 0 {
+                    let b = next.trailing_zeros() as usize;
+                    next &= next - 1;
+                    seen |= 1 << b;
+                    parent[b] = a;
+                    queue[tail] = b;
+                    tail += 1;
+                }
+            }
+            None
+        }
+
+        fn report_recursion(&mut self, thread: usize, prev: Held, kind: Kind, site: Site) {
+            report!("lockdep: recursive locking detected (thread {})", thread);
+            if prev.kind == Kind::Shared && kind == Kind::Shared {
+                report!("  read lock: a writer waiting in between deadlocks it");
+            }
+            report!(
+                "  lock {:#x} already held, acquired at {}",
+                self.keys[prev.class],
+                prev.site
+            );
+            report!("  acquired again at {}", site);
+            self.disabled = true;
+        }
+
+        fn report_inversion(&mut self, thread: usize, held: Held, class: usize, site: Site) {
+            report!(
+                "lockdep: possible deadlock, inverted lock order (thread {})",
+                thread
+            );
+            report!(
+                "  holding lock {:#x}, acquired at {}",
+                self.keys[held.class],
+                held.site
+            );
+            report!("  acquiring lock {:#x} at {}", self.keys[class], site);
+            report!("  but the opposite order was seen before:");
+            if let Some(parent) = self.find_path(class, held.class) {
+                // Walk back from the held lock to the new one, printing each edge.
+                let mut b = held.class;
+                while b != class {
+                    let a = parent[b];
+                    if let Some((sa, sb)) = self.dep_sites[a][b] {
+                        report!("    lock {:#x} acquired at {}", self.keys[a], sa);
+                        report!("    then lock {:#x} acquired at {}", self.keys[b], sb);
+                    }
+                    b = a;
+                }
+            }
+            self.disabled = true;
+        }
+
+        fn acquire(&mut self, key: usize, kind: Kind, blocking: bool, site: Site) {
+            let thread = sched::current().as_usize();
+            let Some(class) = self.class_of(key) else {
+                return;
+            };
+            let len = self.held_len[thread];
+            // A successful try_* cannot have deadlocked: only record it as held.
+            if blocking {
+                for i in 0..len {
+                    let Some(h) = self.held[thread][i] else {
+                        continue;
+                    };
+                    if h.class == class {
+                        self.report_recursion(thread, h, kind, site);
+                        return;
+                    }
+                    if self.find_path(class, h.class).is_some() {
+                        self.report_inversion(thread, h, class, site);
+                        return;
+                    }
+                }
+                for i in 0..len {
+                    let Some(h) = self.held[thread][i] else {
+                        continue;
+                    };
+                    if self.deps[h.class] & (1 << class) == 0 {
+                        self.deps[h.class] |= 1 << class;
+                        self.dep_sites[h.class][class] = Some((h.site, site));
+                    }
+                }
+            }
+            if len == MAX_HELD {
+                report!("lockdep: more than {} locks held, turning off", MAX_HELD);
+                self.disabled = true;
+                return;
+            }
+            self.held[thread][len] = Some(Held { class, kind, site });
+            self.held_len[thread] = len + 1;
+        }
+
+        fn release(&mut self, key: usize) {
+            let thread = sched::current().as_usize();
+            let len = self.held_len[thread];
+            let held = &mut self.held[thread];
+            // Most recent acquisition first; unlocking out of order is allowed.
+            let Some(i) = (0..len)
+                .rev()
+                .find(|&i| held[i].is_some_and(|h| self.keys[h.class] == key))
+            else {
+                return;
+            };
+            held.copy_within(i + 1..len, i);
+            held[len - 1] = None;
+            self.held_len[thread] = len - 1;
+        }
+    }
+
+    /// Records that the calling thread takes lock `key`; call before spinning.
+    #[track_caller]
+    pub fn acquire(key: usize, kind: Kind) {
+        let site = Location::caller();
+        with_state(|st| st.acquire(key, kind, true, site));
+    }
+
+    /// Records a successful `try_*` acquisition of lock `key`.
+    #[track_caller]
+    pub fn acquired_try(key: usize, kind: Kind) {
+        let site = Location::caller();
+        with_state(|st| st.acquire(key, kind, false, site));
+    }
+
+    /// Records that lock `key` was released.
+    pub fn release(key: usize) {
+        with_state(|st| st.release(key));
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const A: usize = 0x1000;
+        const B: usize = 0x2000;
+
+        /// Forgets every lock. Stale `keys`, `held` and `dep_sites` entries
+        /// are never read past `nclasses`, `held_len` and `deps`.
+        fn reset() {
+            let st = unsafe { &mut *STATE.0.get() };
+            st.nclasses = 0;
+            st.deps = [0; MAX_CLASSES];
+            st.held_len = [0; MAX_THREADS];
+            st.disabled = false;
+        }
+
+        fn reported() -> bool {
+            unsafe { (*STATE.0.get()).disabled }
+        }
+
+        #[test_case]
+        fn second_read_of_a_held_lock_is_recursion() {
+            reset();
+            acquire(A, Kind::Shared);
+            acquire(B, Kind::Shared);
+            assert!(!reported());
+            acquire(A, Kind::Shared);
+            assert!(reported());
+            reset();
+        }
+
+        #[test_case]
+        fn second_lock_of_a_held_lock_is_recursion() {
+            reset();
+            acquire(A, Kind::Exclusive);
+            // A try cannot deadlock: it is only recorded.
+            acquired_try(A, Kind::Exclusive);
+            assert!(!reported());
+            acquire(A, Kind::Exclusive);
+            assert!(reported());
+            reset();
+        }
+
+        #[test_case]
+        fn opposite_order_is_an_inversion() {
+            reset();
+            acquire(A, Kind::Exclusive);
+            acquire(B, Kind::Shared);
+            release(B);
+            release(A);
+            // Same order again, and B alone: fine.
+            acquire(A, Kind::Shared);
+            acquire(B, Kind::Exclusive);
+            release(A);
+            release(B);
+            acquire(B, Kind::Exclusive);
+            assert!(!reported());
+            acquire(A, Kind::Exclusive);
+            assert!(reported());
+            reset();
+        }
+    }
+}
@@ -1 +1,17 @@
+pub mod condvar;
+pub mod irq;
+pub mod irq_spinlock;
+pub mod lockdep;
+pub mod mutex;
+pub mod once;
+pub mod rwlock;
+pub mod semaphore;
 pub mod spinlock;
+pub mod ticket_lock;
+pub mod wait_queue;
+
+pub use condvar::Condvar;
+pub use mutex::{Mutex, MutexGuard};
+pub use once::{Lazy, Once, OnceCell};
+pub use semaphore::Semaphore;
+pub use wait_queue::WaitQueue;
@@ -0,0 +1,131 @@
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::lockdep::{self, Kind};
+use super::wait_queue::WaitQueue;
+
+/// A sleeping mutual-exclusion lock.
+/// - Contended `lock` puts the calling kernel thread to sleep instead of
+///   spinning; it is woken when the holder unlocks.
+/// - Thread context only: an interrupt handler may use `try_lock`, never `lock`.
+/// - Not recursive: locking twice from the same thread deadlocks.
+pub struct Mutex<T> {
+    locked: AtomicBool,
+    waiters: WaitQueue,
+    data: UnsafeCell<T>,
+}
+
+// Soundness: same as SpinLock, access to T is gated by the lock bit.
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            waiters: WaitQueue::new(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Sleeps until the lock is acquired.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        lockdep::acquire(self.key(), Kind::Exclusive);
+        loop {
+            if let Some(guard) = self.raw_try_lock() {
+                return guard;
+            }
+            self.waiters
+                .wait_until(|| !self.locked.load(Ordering::Relaxed));
+        }
+    }
+
+    /// Attempts to acquire the lock without sleeping.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        let guard = self.raw_try_lock()?;
+        lockdep::acquired_try(self.key(), Kind::Exclusive);
+        Some(guard)
+    }
+
+    fn raw_try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        Some(MutexGuard {
+            mutex: self,
+            _nosend: PhantomData,
+        })
+    }
+
+    /// Returns whether the lock is held (best-effort; do not rely for logic).
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// Identity of this lock for lockdep.
+    fn key(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    fn unlock(&self) {
+        lockdep::release(self.key());
+        self.locked.store(false, Ordering::Release);
+        self.waiters.wake_one();
+    }
+}
+
+/// RAII guard for `Mutex`.
+pub struct MutexGuard<'a, T> {
+    pub(super) mutex: &'a Mutex<T>,
+    _nosend: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> core::ops::Deref for MutexGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // Safety: we hold the lock; exclusive access is enforced by the protocol.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T> core::ops::DerefMut for MutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safety: we hold the lock exclusively; no aliasing mutable refs.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T> Drop for MutexGuard<'a, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+#[cfg(all(test, kernel))]
+mod tests {
+    use super::*;
+    use crate::subsystems::sched::{self, tests::join};
+
+    #[test_case]
+    fn contended_increments_are_not_lost() {
+        static COUNTER: Mutex<u32> = Mutex::new(0);
+        fn add() {
+            for _ in 0..100 {
+                let mut n = COUNTER.lock();
+                let v = *n;
+                // The other thread runs into the held lock and sleeps.
+                sched::yield_now();
+                *n = v + 1;
+            }
+        }
+        let ids = [
+            sched::spawn("add", add).unwrap(),
+            sched::spawn("add", add).unwrap(),
+        ];
+        join(&ids);
+        assert!(!COUNTER.is_locked());
+        assert_eq!(*COUNTER.lock(), 200);
+    }
+}
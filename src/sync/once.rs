@@ -0,0 +1,146 @@
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+const INCOMPLETE: u8 = 0;
+const RUNNING: u8 = 1;
+const COMPLETE: u8 = 2;
+
+/// One-time initialization primitive.
+/// - The first `call_once` runs its closure; later callers spin until it has
+///   finished, then return without running theirs.
+/// - Calling `call_once` on the same `Once` from inside the closure (or from an
+///   interrupt handler that preempted it) deadlocks.
+pub struct Once {
+    state: AtomicU8,
+}
+
+impl Once {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(INCOMPLETE),
+        }
+    }
+
+    pub fn call_once<F: FnOnce()>(&self, f: F) {
+        if self
+            .state
+            .compare_exchange(INCOMPLETE, RUNNING, Ordering::Acquire, Ordering::Acquire)
+            .is_ok()
+        {
+            f();
+            // Release publishes whatever `f` initialized to every later caller.
+            self.state.store(COMPLETE, Ordering::Release);
+            return;
+        }
+        while self.state.load(Ordering::Acquire) != COMPLETE {
+            core::hint::spin_loop();
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+}
+
+impl Default for Once {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A cell written at most once, then readable by shared reference forever.
+pub struct OnceCell<T> {
+    once: Once,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// Soundness: the value is written once under `Once`, then only shared (&T).
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+
+impl<T> OnceCell<T> {
+    pub const fn new() -> Self {
+        Self {
+            once: Once::new(),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// The value, if it has been set.
+    pub fn get(&self) -> Option<&T> {
+        if self.once.is_completed() {
+            // Safety: COMPLETE is only stored after the value was written.
+            Some(unsafe { (*self.value.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Stores `value` unless the cell is already set, in which case it is handed back.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        self.get_or_init(|| value.take().unwrap());
+        match value {
+            None => Ok(()),
+            Some(v) => Err(v),
+        }
+    }
+
+    /// The value, initializing it with `f` first if it is not set yet.
+    pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+        self.once.call_once(|| {
+            // Safety: only the single `call_once` winner gets here.
+            unsafe { (*self.value.get()).write(f()) };
+        });
+        // Safety: call_once only returns once the value is written.
+        unsafe { (*self.value.get()).assume_init_ref() }
+    }
+}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for OnceCell<T> {
+    fn drop(&mut self) {
+        if self.once.is_completed() {
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+/// A value computed by `init` on first access (a `no_std` `LazyLock`).
+pub struct Lazy<T, F = fn() -> T> {
+    cell: OnceCell<T>,
+    init: UnsafeCell<Option<F>>,
+}
+
+// Soundness: `init` is only taken by the `Once` winner; F may run on any thread.
+unsafe impl<T: Send + Sync, F: Send> Sync for Lazy<T, F> {}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    pub const fn new(init: F) -> Self {
+        Self {
+            cell: OnceCell::new(),
+            init: UnsafeCell::new(Some(init)),
+        }
+    }
+
+    /// Runs the initializer if needed and returns the value.
+    pub fn force(this: &Self) -> &T {
+        this.cell.get_or_init(|| {
+            // Safety: we are inside the cell's call_once, nobody else touches `init`.
+            let init = unsafe { (*this.init.get()).take() };
+            init.expect("Lazy initializer already consumed")()
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> core::ops::Deref for Lazy<T, F> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        Lazy::force(self)
+    }
+}
@@ -0,0 +1,204 @@
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::lockdep::{self, Kind};
+
+/// `state` bit set while a writer holds the lock; the low bits count readers.
+const WRITER: usize = 1 << (usize::BITS - 1);
+
+/// A reader-writer spinlock with writer preference.
+/// - Any number of readers, or a single writer.
+/// - Once a writer is waiting, new readers hold off, so a steady stream of
+///   readers cannot starve it. Intended for read-mostly data.
+/// - Not IRQ-safe, same rules as `SpinLock`.
+pub struct RwSpinLock<T> {
+    state: AtomicUsize,
+    writers_waiting: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+// Soundness: readers share &T across threads (needs Sync), a writer may move T (needs Send).
+unsafe impl<T: Send + Sync> Sync for RwSpinLock<T> {}
+
+impl<T> RwSpinLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            writers_waiting: AtomicUsize::new(0),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Blocks until shared access is granted. Do not call while holding a
+    /// read guard of the same lock if a writer may be waiting (deadlock).
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn read(&self) -> RwReadGuard<'_, T> {
+        lockdep::acquire(self.key(), Kind::Shared);
+        loop {
+            if let Some(guard) = self.raw_try_read() {
+                return guard;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Shared access if no writer holds or waits for the lock.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn try_read(&self) -> Option<RwReadGuard<'_, T>> {
+        let guard = self.raw_try_read()?;
+        lockdep::acquired_try(self.key(), Kind::Shared);
+        Some(guard)
+    }
+
+    fn raw_try_read(&self) -> Option<RwReadGuard<'_, T>> {
+        if self.writers_waiting.load(Ordering::Relaxed) != 0 {
+            return None;
+        }
+        let state = self.state.load(Ordering::Relaxed);
+        if state & WRITER != 0 {
+            return None;
+        }
+        self.state
+            .compare_exchange(state, state + 1, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        Some(RwReadGuard {
+            lock: self,
+            _nosend: PhantomData,
+        })
+    }
+
+    /// Blocks until exclusive access is granted. Non-recursive.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn write(&self) -> RwWriteGuard<'_, T> {
+        lockdep::acquire(self.key(), Kind::Exclusive);
+        self.writers_waiting.fetch_add(1, Ordering::Relaxed);
+        while self
+            .state
+            .compare_exchange_weak(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        self.writers_waiting.fetch_sub(1, Ordering::Relaxed);
+        RwWriteGuard {
+            lock: self,
+            _nosend: PhantomData,
+        }
+    }
+
+    /// Exclusive access if nobody holds the lock.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn try_write(&self) -> Option<RwWriteGuard<'_, T>> {
+        self.state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        lockdep::acquired_try(self.key(), Kind::Exclusive);
+        Some(RwWriteGuard {
+            lock: self,
+            _nosend: PhantomData,
+        })
+    }
+
+    /// Identity of this lock for lockdep.
+    fn key(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Number of readers currently holding the lock (best-effort).
+    pub fn reader_count(&self) -> usize {
+        self.state.load(Ordering::Relaxed) & !WRITER
+    }
+
+    /// Returns whether a writer holds the lock (best-effort; do not rely for logic).
+    pub fn is_write_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER != 0
+    }
+}
+
+/// RAII shared guard for `RwSpinLock`.
+pub struct RwReadGuard<'a, T> {
+    lock: &'a RwSpinLock<T>,
+    _nosend: PhantomData<&'a T>,
+}
+
+impl<'a, T> core::ops::Deref for RwReadGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // Safety: no writer can hold the lock while we are counted as a reader.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for RwReadGuard<'a, T> {
+    fn drop(&mut self) {
+        lockdep::release(self.lock.key());
+        self.lock.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// RAII exclusive guard for `RwSpinLock`.
+pub struct RwWriteGuard<'a, T> {
+    lock: &'a RwSpinLock<T>,
+    _nosend: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> core::ops::Deref for RwWriteGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // Safety: we hold the lock exclusively.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> core::ops::DerefMut for RwWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safety: we hold the lock exclusively; no aliasing refs.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for RwWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        lockdep::release(self.lock.key());
+        self.lock.state.store(0, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn readers_share_and_writers_exclude() {
+        let lock = RwSpinLock::new(0);
+        let a = lock.try_read().unwrap();
+        let b = lock.read();
+        assert_eq!(lock.reader_count(), 2);
+        assert!(lock.try_write().is_none());
+        drop((a, b));
+        assert_eq!(lock.reader_count(), 0);
+
+        let mut w = lock.try_write().unwrap();
+        *w = 7;
+        assert!(lock.is_write_locked());
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_write().is_none());
+        drop(w);
+        assert!(!lock.is_write_locked());
+        assert_eq!(*lock.try_read().unwrap(), 7);
+    }
+
+    #[test_case]
+    fn waiting_writer_holds_off_new_readers() {
+        let lock = RwSpinLock::new(());
+        let reader = lock.read();
+        // What `write` does before spinning on the reader.
+        lock.writers_waiting.fetch_add(1, Ordering::Relaxed);
+        assert!(lock.try_read().is_none());
+        drop(reader);
+        assert!(lock.try_read().is_none());
+        lock.writers_waiting.fetch_sub(1, Ordering::Relaxed);
+        assert!(lock.try_read().is_some());
+    }
+}
@@ -0,0 +1,89 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::wait_queue::WaitQueue;
+
+/// A counting semaphore.
+/// - `acquire` takes a unit, sleeping while none is available.
+/// - `release` may be called from interrupt handlers, e.g. to signal that
+///   input arrived.
+pub struct Semaphore {
+    count: AtomicUsize,
+    waiters: WaitQueue,
+}
+
+impl Semaphore {
+    pub const fn new(count: usize) -> Self {
+        Self {
+            count: AtomicUsize::new(count),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// Sleeps until a unit is available, then takes it.
+    pub fn acquire(&self) {
+        loop {
+            if self.try_acquire() {
+                return;
+            }
+            self.waiters
+                .wait_until(|| self.count.load(Ordering::Relaxed) != 0);
+        }
+    }
+
+    /// Takes a unit if one is available, without sleeping.
+    pub fn try_acquire(&self) -> bool {
+        self.count
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok()
+    }
+
+    /// Returns a unit and wakes one waiter.
+    pub fn release(&self) {
+        self.count.fetch_add(1, Ordering::Release);
+        self.waiters.wake_one();
+    }
+
+    /// Units currently available (best-effort).
+    pub fn available(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(all(test, kernel))]
+mod tests {
+    use super::*;
+    use crate::subsystems::sched::{self, tests::join, ThreadState};
+
+    #[test_case]
+    fn acquire_sleeps_until_a_unit_is_released() {
+        static UNITS: Semaphore = Semaphore::new(2);
+        static TAKEN: AtomicUsize = AtomicUsize::new(0);
+        fn taker() {
+            UNITS.acquire();
+            TAKEN.fetch_add(1, Ordering::Relaxed);
+        }
+        assert!(UNITS.try_acquire());
+        assert!(UNITS.try_acquire());
+        assert!(!UNITS.try_acquire());
+        let ids = [
+            sched::spawn("taker", taker).unwrap(),
+            sched::spawn("taker", taker).unwrap(),
+        ];
+        sched::yield_now();
+        assert!(ids
+            .iter()
+            .all(|&id| sched::state(id) == Some(ThreadState::Blocked)));
+
+        // One unit, one waiter: the first one in.
+        UNITS.release();
+        join(&ids[..1]);
+        assert_eq!(TAKEN.load(Ordering::Relaxed), 1);
+        assert_eq!(sched::state(ids[1]), Some(ThreadState::Blocked));
+
+        UNITS.release();
+        UNITS.release();
+        join(&ids);
+        assert_eq!(TAKEN.load(Ordering::Relaxed), 2);
+        assert_eq!(UNITS.available(), 1);
+    }
+}
@@ -2,10 +2,12 @@ use core::cell::UnsafeCell;
 use core::marker::PhantomData;
 use core::sync::atomic::{AtomicBool, Ordering};
 
+use super::lockdep::{self, Kind};
+
 /// A minimal, non-recursive spinlock.
 /// - Provides Acquire/Release semantics for mutual exclusion.
 /// - Not fair (may starve under heavy contention).
-/// - Not IRQ-safe: do **not** use from both normal and interrupt context on the same CPU without an IRQ-disabling wrapper.
+/// - Not IRQ-safe: do **not** use from both normal and interrupt context on the same CPU; use `IrqSpinLock` for that.
 /// - Intended for early boot or very short critical sections.
 pub struct SpinLock<T> {
     locked: AtomicBool,
@@ -26,7 +28,9 @@ impl<T> SpinLock<T> {
     }
 
     /// Blocks until the lock is acquired. Non-recursive.
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub fn lock(&self) -> SpinGuard<'_, T> {
+        lockdep::acquire(self.key(), Kind::Exclusive);
         while self
             .locked
             .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
@@ -41,12 +45,14 @@ impl<T> SpinLock<T> {
     }
 
     /// Attempts to acquire the lock without blocking.
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub fn try_lock(&self) -> Option<SpinGuard<'_, T>> {
         if self
             .locked
             .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
             .is_ok()
         {
+            lockdep::acquired_try(self.key(), Kind::Exclusive);
             Some(SpinGuard {
                 lock: self,
                 _nosend: PhantomData,
@@ -61,7 +67,22 @@ impl<T> SpinLock<T> {
         self.locked.load(Ordering::Relaxed)
     }
 
+    /// Clears the lock bit whoever holds it.
+    ///
+    /// # Safety
+    /// The holder must never touch the data again. Only meant for the panic
+    /// path, which takes over the console from code that will not resume.
+    pub unsafe fn force_unlock(&self) {
+        self.unlock();
+    }
+
+    /// Identity of this lock for lockdep.
+    fn key(&self) -> usize {
+        self as *const Self as usize
+    }
+
     fn unlock(&self) {
+        lockdep::release(self.key());
         // Release publishes all writes to the protected data before clearing the bit.
         self.locked.store(false, Ordering::Release);
     }
@@ -94,3 +115,26 @@ impl<'a, T> Drop for SpinGuard<'a, T> {
         self.lock.unlock();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn try_lock_fails_while_held() {
+        let lock = SpinLock::new(0);
+        let guard = lock.lock();
+        assert!(lock.is_locked());
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+        assert!(!lock.is_locked());
+        assert!(lock.try_lock().is_some());
+    }
+
+    #[test_case]
+    fn writes_through_the_guard_persist() {
+        let lock = SpinLock::new(1);
+        *lock.lock() += 41;
+        assert_eq!(*lock.lock(), 42);
+    }
+}
@@ -0,0 +1,148 @@
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::lockdep::{self, Kind};
+
+/// A FIFO spinlock.
+/// - Each caller takes a ticket and waits until it is served, so the lock is
+///   granted in arrival order and no waiter can starve.
+/// - Slightly more expensive than `SpinLock` when uncontended.
+/// - Not IRQ-safe, same rules as `SpinLock`.
+pub struct TicketLock<T> {
+    next_ticket: AtomicUsize,
+    now_serving: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+// Soundness: same argument as SpinLock, exclusive access is gated by the ticket.
+unsafe impl<T: Send> Sync for TicketLock<T> {}
+
+impl<T> TicketLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            next_ticket: AtomicUsize::new(0),
+            now_serving: AtomicUsize::new(0),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Blocks until every earlier caller has been served. Non-recursive.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn lock(&self) -> TicketGuard<'_, T> {
+        lockdep::acquire(self.key(), Kind::Exclusive);
+        // Counters wrap around; only equality is ever compared.
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            core::hint::spin_loop();
+        }
+        TicketGuard {
+            lock: self,
+            _nosend: PhantomData,
+        }
+    }
+
+    /// Takes the lock only if it is free and nobody is queued.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn try_lock(&self) -> Option<TicketGuard<'_, T>> {
+        let serving = self.now_serving.load(Ordering::Relaxed);
+        self.next_ticket
+            .compare_exchange(
+                serving,
+                serving.wrapping_add(1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .ok()?;
+        lockdep::acquired_try(self.key(), Kind::Exclusive);
+        Some(TicketGuard {
+            lock: self,
+            _nosend: PhantomData,
+        })
+    }
+
+    /// Returns whether the lock is held (best-effort; do not rely for logic).
+    pub fn is_locked(&self) -> bool {
+        self.next_ticket.load(Ordering::Relaxed) != self.now_serving.load(Ordering::Relaxed)
+    }
+
+    /// Number of callers holding or waiting for the lock (best-effort).
+    pub fn queue_len(&self) -> usize {
+        self.next_ticket
+            .load(Ordering::Relaxed)
+            .wrapping_sub(self.now_serving.load(Ordering::Relaxed))
+    }
+
+    /// Identity of this lock for lockdep.
+    fn key(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    fn unlock(&self) {
+        lockdep::release(self.key());
+        // Only the holder writes now_serving, so a plain increment is enough.
+        let next = self.now_serving.load(Ordering::Relaxed).wrapping_add(1);
+        self.now_serving.store(next, Ordering::Release);
+    }
+}
+
+/// RAII guard for `TicketLock`.
+pub struct TicketGuard<'a, T> {
+    lock: &'a TicketLock<T>,
+    _nosend: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> core::ops::Deref for TicketGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // Safety: we hold the current ticket; exclusive access is enforced by the protocol.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> core::ops::DerefMut for TicketGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safety: we hold the current ticket; no aliasing mutable refs.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for TicketGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn try_lock_fails_while_held() {
+        let lock = TicketLock::new(0);
+        let mut guard = lock.lock();
+        *guard = 1;
+        assert!(lock.is_locked());
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+        assert!(!lock.is_locked());
+        assert_eq!(*lock.try_lock().unwrap(), 1);
+        assert_eq!(lock.queue_len(), 0);
+    }
+
+    #[test_case]
+    fn release_goes_to_the_next_ticket() {
+        let lock = TicketLock::new(());
+        let guard = lock.lock();
+        // A second caller queued behind the holder, as `lock` would take it.
+        let waiter = lock.next_ticket.fetch_add(1, Ordering::Relaxed);
+        assert_eq!(lock.queue_len(), 2);
+        drop(guard);
+        // The queued ticket is served, not a later `try_lock`.
+        assert_eq!(lock.now_serving.load(Ordering::Relaxed), waiter);
+        assert!(lock.is_locked());
+        assert!(lock.try_lock().is_none());
+        lock.now_serving.fetch_add(1, Ordering::Relaxed);
+        assert!(lock.try_lock().is_some());
+    }
+}
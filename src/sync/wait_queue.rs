@@ -0,0 +1,113 @@
+use super::irq::IrqDisabled;
+use super::irq_spinlock::IrqSpinLock;
+use crate::subsystems::sched::{self, ThreadId, MAX_THREADS};
+
+/// FIFO of sleeping threads; a thread sits in at most one queue at a time,
+/// so `MAX_THREADS` slots always suffice.
+struct Waiters {
+    ids: [ThreadId; MAX_THREADS],
+    len: usize,
+}
+
+impl Waiters {
+    fn push(&mut self, id: ThreadId) {
+        if self.len < MAX_THREADS && !self.ids[..self.len].contains(&id) {
+            self.ids[self.len] = id;
+            self.len += 1;
+        }
+    }
+
+    fn pop(&mut self) -> Option<ThreadId> {
+        if self.len == 0 {
+            return None;
+        }
+        let id = self.ids[0];
+        self.ids.copy_within(1..self.len, 0);
+        self.len -= 1;
+        Some(id)
+    }
+
+    fn remove(&mut self, id: ThreadId) {
+        if let Some(i) = self.ids[..self.len].iter().position(|&w| w == id) {
+            self.ids.copy_within(i + 1..self.len, i);
+            self.len -= 1;
+        }
+    }
+}
+
+/// Threads sleeping until some event; the building block of the blocking primitives.
+/// - `wake_one`/`wake_all` may be called from interrupt handlers.
+/// - Waiting must happen in thread context, never in an interrupt handler.
+pub struct WaitQueue {
+    waiters: IrqSpinLock<Waiters>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiters: IrqSpinLock::new(Waiters {
+                ids: [ThreadId::BOOT; MAX_THREADS],
+                len: 0,
+            }),
+        }
+    }
+
+    /// Sleeps until `cond` returns `true`, re-checking it after every wakeup.
+    ///
+    /// `cond` runs with interrupts disabled, so a wakeup from an interrupt
+    /// handler cannot slip in between the check and going to sleep.
+    pub fn wait_until(&self, mut cond: impl FnMut() -> bool) {
+        loop {
+            let _irq = IrqDisabled::new();
+            if cond() {
+                return;
+            }
+            self.sleep_after(|| {});
+        }
+    }
+
+    /// Queues the current thread, runs `before_sleep` (e.g. releasing a lock)
+    /// and sleeps until woken. Wakeups sent after the thread was queued are
+    /// never lost, but it may return spuriously.
+    pub fn sleep_after(&self, before_sleep: impl FnOnce()) {
+        let _irq = IrqDisabled::new();
+        let me = sched::current();
+        self.waiters.lock().push(me);
+        before_sleep();
+        sched::block_current();
+        // Woken by someone else than this queue: do not leave a stale entry.
+        self.waiters.lock().remove(me);
+    }
+
+    /// Wakes the longest-waiting thread. Returns `false` if nobody was waiting.
+    pub fn wake_one(&self) -> bool {
+        loop {
+            let Some(id) = self.waiters.lock().pop() else {
+                return false;
+            };
+            // Entries that are no longer asleep are skipped.
+            if sched::wake(id) {
+                return true;
+            }
+        }
+    }
+
+    /// Wakes every waiting thread, returning how many there were.
+    pub fn wake_all(&self) -> usize {
+        let mut n = 0;
+        while self.wake_one() {
+            n += 1;
+        }
+        n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiters.lock().len == 0
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}